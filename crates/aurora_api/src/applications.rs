@@ -0,0 +1,457 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use aurora_db::{
+    actor::Actor,
+    application::{Application, ApplicationCommand, CommandOption},
+    guild::Guild,
+    guild_member::GuildMember,
+    FromId,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, patch},
+    Json, Router,
+};
+use jsonwebtoken::EncodingKey;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use sqlx::types::Json as SqlJson;
+
+use crate::{
+    channels::double_option,
+    commands::verify_command,
+    error::{ErrorMessage, OVTError},
+    flags::{AccountFlags, GuildPermissions},
+    guilds::verify_permissions,
+    net::parse_public_url,
+    state::OVTState,
+    token::{get_user, Claims},
+};
+
+/// Maximum number of commands an application can have globally, or in a single guild.
+pub const MAX_APPLICATION_COMMANDS: i64 = 100;
+
+fn random_secret() -> String {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Fetches an application which `actor` is either the owner or the bot of.
+pub async fn get_application(
+    db: &sqlx::PgPool,
+    application_id: String,
+    actor: &Actor,
+) -> Result<Application, (StatusCode, Json<ErrorMessage>)> {
+    let application = Application::from_id(db, application_id)
+        .await
+        .map_err(|_| OVTError::ApplicationNotFound.to_resp())?;
+
+    if application.owner_id != actor.id && application.bot_id != actor.id {
+        return Err(OVTError::ApplicationNotFound.to_resp());
+    }
+
+    Ok(application)
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateApplication {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 32)]
+    name: String,
+    /// The bot's username, following the same rules as everyone else's.
+    #[validate(pattern = r"^[a-b0-9_-]+$")]
+    #[validate(min_length = 3)]
+    #[validate(max_length = 32)]
+    username: String,
+    #[serde(default)]
+    interactions_url: Option<String>,
+}
+
+/// A freshly created application, the only time its bot token and signing secret are shown.
+#[derive(Serialize)]
+pub struct CreatedApplication {
+    #[serde(flatten)]
+    application: Application,
+    bot_token: String,
+    interactions_secret: String,
+}
+
+/// Creates an application along with the bot account it acts as.
+pub async fn create_application(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateApplication>,
+) -> Result<Json<CreatedApplication>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (_, account) = get_user(&headers, &state.key, &state.pg).await?;
+    if let Some(url) = &model.interactions_url {
+        parse_public_url(url)
+            .await
+            .ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+
+    let bot_id = uuid7::uuid7().to_string();
+    let session_id = uuid7::uuid7().to_string();
+    let interactions_secret = random_secret();
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    sqlx::query!(
+        "INSERT INTO actors (id, username) VALUES ($1, $2);",
+        &bot_id,
+        &model.username
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    sqlx::query!(
+        "INSERT INTO accounts (id, actor_id, flags) VALUES ($1, $1, $2);",
+        &bot_id,
+        AccountFlags::BOT.bits()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "INSERT INTO account_settings (id, theme) VALUES ($1, 'dark');",
+        &bot_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "INSERT INTO sessions (id, user_id) VALUES ($1, $2);",
+        &session_id,
+        &bot_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let application = sqlx::query_as!(
+        Application,
+        "INSERT INTO applications (id, owner_id, bot_id, name, interactions_url, interactions_secret) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &account.id,
+        &bot_id,
+        model.name.trim(),
+        model.interactions_url,
+        &interactions_secret
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let time = chrono::Utc::now().timestamp_micros() as u128;
+    let claims = Claims {
+        sub: session_id,
+        exp: (time + Duration::from_weeks(6).as_micros()) as usize,
+        iat: time as usize,
+    };
+    let bot_token = claims
+        .make_token(&EncodingKey::from_secret(state.key.as_bytes()))
+        .map_err(|err| err.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(CreatedApplication {
+        application,
+        bot_token,
+        interactions_secret,
+    }))
+}
+
+pub async fn get_applications(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Application>>, (StatusCode, Json<ErrorMessage>)> {
+    let (_, account) = get_user(&headers, &state.key, &state.pg).await?;
+
+    let applications = Application::from_owner(&state.pg, &account.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(applications))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ModifyApplication {
+    #[serde(default)]
+    #[validate(min_length = 1)]
+    #[validate(max_length = 32)]
+    name: Option<String>,
+    /// `null` sends interactions over the gateway again, leaving it out keeps the current url.
+    #[serde(default, deserialize_with = "double_option")]
+    interactions_url: Option<Option<String>>,
+}
+
+pub async fn modify_application(
+    headers: HeaderMap,
+    Path(application_id): Path<String>,
+    State(state): State<OVTState>,
+    Json(model): Json<ModifyApplication>,
+) -> Result<Json<Application>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    if let Some(Some(url)) = &model.interactions_url {
+        parse_public_url(url)
+            .await
+            .ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+    let application = get_application(&state.pg, application_id, &actor).await?;
+
+    let interactions_url = match model.interactions_url {
+        Some(interactions_url) => interactions_url,
+        None => application.interactions_url,
+    };
+
+    let modified_application = sqlx::query_as!(
+        Application,
+        "UPDATE applications SET name = COALESCE($1, name), interactions_url = $2 WHERE id = $3 RETURNING *;",
+        model.name.as_deref().map(str::trim),
+        interactions_url,
+        &application.id
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(modified_application))
+}
+
+#[derive(Deserialize)]
+pub struct CreateCommand {
+    name: String,
+    description: String,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+}
+
+/// Registers a command, replacing any existing one with the same name in the same scope.
+async fn create_command(
+    state: &OVTState,
+    application: &Application,
+    guild_id: Option<&str>,
+    model: CreateCommand,
+) -> Result<Json<ApplicationCommand>, (StatusCode, Json<ErrorMessage>)> {
+    verify_command(&model.name, &model.description, &model.options)
+        .map_err(|_| OVTError::InvalidCommand.to_resp())?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // locks the application so concurrent registrations can't go over the limit
+    sqlx::query!(
+        "SELECT id FROM applications WHERE id = $1 FOR UPDATE;",
+        &application.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let commands = sqlx::query_scalar!(
+        "SELECT COUNT(*) AS \"count!\" FROM application_commands WHERE application_id = $1 AND guild_id IS NOT DISTINCT FROM $2 AND name != $3;",
+        &application.id,
+        guild_id,
+        &model.name
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    if commands >= MAX_APPLICATION_COMMANDS {
+        return Err(OVTError::MaximumCommandsReached.to_resp());
+    }
+
+    let command = sqlx::query_as!(
+        ApplicationCommand,
+        r#"INSERT INTO application_commands (id, application_id, guild_id, name, description, options) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (application_id, guild_id, name) DO UPDATE SET description = $5, options = $6
+        RETURNING id, application_id, guild_id, name, description, options AS "options: SqlJson<Vec<CommandOption>>", created_at;"#,
+        uuid7::uuid7().to_string(),
+        &application.id,
+        guild_id,
+        &model.name,
+        &model.description,
+        SqlJson(&model.options) as _
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(command))
+}
+
+pub async fn create_global_command(
+    headers: HeaderMap,
+    Path(application_id): Path<String>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateCommand>,
+) -> Result<Json<ApplicationCommand>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let application = get_application(&state.pg, application_id, &actor).await?;
+
+    create_command(&state, &application, None, model).await
+}
+
+pub async fn get_global_commands(
+    headers: HeaderMap,
+    Path(application_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ApplicationCommand>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let application = get_application(&state.pg, application_id, &actor).await?;
+
+    let commands = ApplicationCommand::from_application(&state.pg, &application.id, None)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(commands))
+}
+
+/// Guild commands only make sense in guilds the application's bot has joined.
+async fn get_bot_guild(
+    db: &sqlx::PgPool,
+    application: &Application,
+    guild_id: String,
+) -> Result<Guild, (StatusCode, Json<ErrorMessage>)> {
+    let guild = Guild::from_id(db, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    GuildMember::from_id(db, (&application.bot_id, &guild.id))
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+
+    Ok(guild)
+}
+
+pub async fn create_guild_command(
+    headers: HeaderMap,
+    Path((application_id, guild_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateCommand>,
+) -> Result<Json<ApplicationCommand>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let application = get_application(&state.pg, application_id, &actor).await?;
+    let guild = get_bot_guild(&state.pg, &application, guild_id).await?;
+
+    create_command(&state, &application, Some(&guild.id), model).await
+}
+
+pub async fn get_guild_commands(
+    headers: HeaderMap,
+    Path((application_id, guild_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ApplicationCommand>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let application = get_application(&state.pg, application_id, &actor).await?;
+    let guild = get_bot_guild(&state.pg, &application, guild_id).await?;
+
+    let commands =
+        ApplicationCommand::from_application(&state.pg, &application.id, Some(&guild.id))
+            .await
+            .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(commands))
+}
+
+pub async fn delete_command(
+    headers: HeaderMap,
+    Path((application_id, command_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let application = get_application(&state.pg, application_id, &actor).await?;
+    let command = ApplicationCommand::from_id(&state.pg, command_id)
+        .await
+        .map_err(|_| OVTError::CommandNotFound.to_resp())?;
+    if command.application_id != application.id {
+        return Err(OVTError::CommandNotFound.to_resp());
+    }
+
+    sqlx::query!(
+        "DELETE FROM application_commands WHERE id = $1;",
+        &command.id
+    )
+    .execute(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+/// Every command members can use in a guild.
+pub async fn get_available_commands(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ApplicationCommand>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    let commands = ApplicationCommand::from_guild(&state.pg, &guild.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(commands))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/applications",
+            get(get_applications).post(create_application),
+        )
+        .route("/applications/:application_id", patch(modify_application))
+        .route(
+            "/applications/:application_id/commands",
+            get(get_global_commands).post(create_global_command),
+        )
+        .route(
+            "/applications/:application_id/commands/:command_id",
+            delete(delete_command),
+        )
+        .route(
+            "/applications/:application_id/guilds/:guild_id/commands",
+            get(get_guild_commands).post(create_guild_command),
+        )
+        .route("/guilds/:guild_id/commands", get(get_available_commands))
+}
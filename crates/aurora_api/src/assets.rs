@@ -0,0 +1,258 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{actor::Actor, guild::Guild, FromId};
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    images::{process_image, ImageKind},
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+/// Maximum size of an uploaded avatar, banner or icon, in bytes.
+pub const MAX_IMAGE_UPLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+/// Processes an uploaded image and stores every size of it, returning the url it's served from.
+async fn store_image(
+    state: &OVTState,
+    data: Bytes,
+    kind: ImageKind,
+) -> Result<String, (StatusCode, Json<ErrorMessage>)> {
+    // decoding and resizing would otherwise hold up the runtime
+    let processed = tokio::task::spawn_blocking(move || process_image(&data, kind))
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?
+        .map_err(|_| OVTError::InvalidImage.to_resp())?;
+
+    for (width, encoded) in processed.variants {
+        let key = format!("{}/{}/{width}.png", kind.prefix(), processed.hash);
+        state
+            .blobs
+            .put(&key, encoded, "image/png")
+            .await
+            .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    }
+
+    Ok(format!("/assets/{}/{}", kind.prefix(), processed.hash))
+}
+
+/// The images a user has, unlike guild icons.
+#[derive(Clone, Copy)]
+enum ActorImage {
+    Avatar,
+    Banner,
+}
+
+async fn set_actor_image(
+    state: &OVTState,
+    actor_id: &str,
+    image: ActorImage,
+    url: Option<String>,
+) -> Result<Actor, (StatusCode, Json<ErrorMessage>)> {
+    let actor = match image {
+        ActorImage::Avatar => {
+            sqlx::query_as!(
+                Actor,
+                "UPDATE actors SET avatar_url = $2 WHERE id = $1 RETURNING *;",
+                actor_id,
+                url
+            )
+            .fetch_one(&state.pg)
+            .await
+        }
+        ActorImage::Banner => {
+            sqlx::query_as!(
+                Actor,
+                "UPDATE actors SET banner_url = $2 WHERE id = $1 RETURNING *;",
+                actor_id,
+                url
+            )
+            .fetch_one(&state.pg)
+            .await
+        }
+    };
+
+    actor.map_err(|_| OVTError::InternalServerError.to_resp())
+}
+
+pub async fn upload_avatar(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+    data: Bytes,
+) -> Result<Json<Actor>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let url = store_image(&state, data, ImageKind::Avatar).await?;
+
+    Ok(Json(
+        set_actor_image(&state, &actor.id, ActorImage::Avatar, Some(url)).await?,
+    ))
+}
+
+pub async fn delete_avatar(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    set_actor_image(&state, &actor.id, ActorImage::Avatar, None).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub async fn upload_banner(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+    data: Bytes,
+) -> Result<Json<Actor>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let url = store_image(&state, data, ImageKind::Banner).await?;
+
+    Ok(Json(
+        set_actor_image(&state, &actor.id, ActorImage::Banner, Some(url)).await?,
+    ))
+}
+
+pub async fn delete_banner(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    set_actor_image(&state, &actor.id, ActorImage::Banner, None).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+async fn set_guild_icon(
+    state: &OVTState,
+    guild: &Guild,
+    url: Option<String>,
+) -> Result<Guild, (StatusCode, Json<ErrorMessage>)> {
+    let modified_guild = sqlx::query_as!(
+        Guild,
+        "UPDATE guilds SET icon_url = $2 WHERE id = $1 RETURNING *;",
+        &guild.id,
+        url
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::GuildUpdate(modified_guild.clone())).await?;
+
+    Ok(modified_guild)
+}
+
+pub async fn upload_guild_icon(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+    data: Bytes,
+) -> Result<Json<Guild>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MODIFY_GUILD).await?;
+
+    let url = store_image(&state, data, ImageKind::GuildIcon).await?;
+
+    Ok(Json(set_guild_icon(&state, &guild, Some(url)).await?))
+}
+
+pub async fn delete_guild_icon(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MODIFY_GUILD).await?;
+
+    set_guild_icon(&state, &guild, None).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AssetQuery {
+    /// The smallest width wanted; the closest stored size at or above it is served.
+    size: Option<u32>,
+}
+
+pub async fn get_asset(
+    Path((prefix, hash)): Path<(String, String)>,
+    Query(query): Query<AssetQuery>,
+    State(state): State<OVTState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorMessage>)> {
+    let kind = ImageKind::from_prefix(&prefix).ok_or_else(|| OVTError::AssetNotFound.to_resp())?;
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(OVTError::AssetNotFound.to_resp());
+    }
+
+    let sizes = kind.sizes();
+    let (width, _) = query
+        .size
+        .and_then(|size| sizes.iter().rev().find(|(width, _)| *width >= size))
+        .unwrap_or(&sizes[0]);
+
+    let data = state
+        .blobs
+        .get(&format!("{prefix}/{hash}/{width}.png"))
+        .await
+        .map_err(|_| OVTError::AssetNotFound.to_resp())?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            // content-addressed, so an asset never changes
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        data,
+    ))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/users/@me/avatar",
+            put(upload_avatar).delete(delete_avatar),
+        )
+        .route(
+            "/users/@me/banner",
+            put(upload_banner).delete(delete_banner),
+        )
+        .route(
+            "/guilds/:guild_id/icon",
+            put(upload_guild_icon).delete(delete_guild_icon),
+        )
+        .route("/assets/:prefix/:hash", get(get_asset))
+        .layer(DefaultBodyLimit::max(MAX_IMAGE_UPLOAD_SIZE))
+}
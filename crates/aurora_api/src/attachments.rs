@@ -0,0 +1,252 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{attachment::Attachment, message::FullMessage, FromId};
+use axum::{
+    extract::{multipart::Field, Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{
+    error::{ErrorMessage, OVTError},
+    state::OVTState,
+    storage::{sign_download, verify_download},
+};
+
+/// Maximum number of files on a single message.
+pub const MAX_ATTACHMENTS: usize = 10;
+/// Maximum size of a single file, in bytes.
+pub const MAX_ATTACHMENT_SIZE: usize = 8 * 1024 * 1024;
+/// Maximum size of a whole message creation request, leaving room for the payload itself.
+pub const MAX_UPLOAD_REQUEST_SIZE: usize = MAX_ATTACHMENTS * MAX_ATTACHMENT_SIZE + 1024 * 1024;
+
+/// How long signed download urls last, in seconds.
+const DOWNLOAD_URL_LIFETIME: i64 = 24 * 60 * 60;
+
+/// Types browsers can't be tricked into running as part of our origin.
+fn is_allowed_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    (essence.starts_with("image/") && essence != "image/svg+xml")
+        || essence.starts_with("video/")
+        || essence.starts_with("audio/")
+        || matches!(
+            essence.as_str(),
+            "text/plain"
+                | "application/pdf"
+                | "application/zip"
+                | "application/json"
+                | "application/octet-stream"
+        )
+}
+
+/// Keeps the last path segment of a filename, restricted to characters safe in urls and keys.
+fn sanitize_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(128)
+        .collect();
+
+    if sanitized.trim_matches('.').is_empty() {
+        "file".to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// A file uploaded alongside a message, not yet stored.
+pub struct Upload {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Upload {
+    /// Reads a multipart file field, stopping as soon as it goes over the size limit.
+    pub async fn from_field(
+        mut field: Field<'_>,
+    ) -> Result<Self, (StatusCode, Json<ErrorMessage>)> {
+        let filename = sanitize_filename(field.file_name().unwrap_or_default());
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        if !is_allowed_content_type(&content_type) {
+            return Err(OVTError::InvalidAttachmentType.to_resp());
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|_| OVTError::InvalidBody.to_resp())?
+        {
+            if data.len() + chunk.len() > MAX_ATTACHMENT_SIZE {
+                return Err(OVTError::AttachmentTooLarge.to_resp());
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(Self {
+            filename,
+            content_type,
+            data,
+        })
+    }
+}
+
+/// An upload which has been written to the blob store and is ready to be inserted.
+pub struct StoredUpload {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i32,
+    pub blob_key: String,
+}
+
+pub async fn store_uploads(
+    state: &OVTState,
+    channel_id: &str,
+    uploads: Vec<Upload>,
+) -> Result<Vec<StoredUpload>, (StatusCode, Json<ErrorMessage>)> {
+    let mut stored: Vec<StoredUpload> = Vec::with_capacity(uploads.len());
+
+    for upload in uploads {
+        let id = uuid7::uuid7().to_string();
+        let blob_key = format!("attachments/{channel_id}/{id}/{}", upload.filename);
+        let size = upload.data.len() as i32;
+
+        if state
+            .blobs
+            .put(&blob_key, upload.data, &upload.content_type)
+            .await
+            .is_err()
+        {
+            delete_blobs(
+                state,
+                stored.into_iter().map(|upload| upload.blob_key).collect(),
+            )
+            .await;
+            return Err(OVTError::InternalServerError.to_resp());
+        }
+
+        stored.push(StoredUpload {
+            id,
+            filename: upload.filename,
+            content_type: upload.content_type,
+            size,
+            blob_key,
+        });
+    }
+
+    Ok(stored)
+}
+
+/// Best-effort cleanup of blobs which no longer belong to anything.
+pub async fn delete_blobs(state: &OVTState, keys: Vec<String>) {
+    for key in keys {
+        let _ = state.blobs.delete(&key).await;
+    }
+}
+
+/// Fills in a fresh signed download url for every attachment.
+pub fn sign_attachments(key: &[u8], messages: &mut [FullMessage]) {
+    // rounded to the hour so urls stay cacheable for a while
+    let expires = (Utc::now().timestamp() / 3600) * 3600 + DOWNLOAD_URL_LIFETIME + 3600;
+
+    for attachment in messages
+        .iter_mut()
+        .flat_map(|message| message.attachments.iter_mut())
+    {
+        let path = format!("{}/{}", attachment.id, attachment.filename);
+        let signature = sign_download(key, &path, expires);
+        attachment.url = format!("/attachments/{path}?expires={expires}&signature={signature}");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    expires: i64,
+    signature: String,
+}
+
+pub async fn download_attachment(
+    Path((attachment_id, filename)): Path<(String, String)>,
+    Query(query): Query<DownloadQuery>,
+    State(state): State<OVTState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorMessage>)> {
+    let path = format!("{attachment_id}/{filename}");
+    if !verify_download(&state.download_key, &path, query.expires, &query.signature) {
+        return Err(OVTError::InvalidSignature.to_resp());
+    }
+
+    let attachment = Attachment::from_id(&state.pg, attachment_id)
+        .await
+        .map_err(|_| OVTError::AttachmentNotFound.to_resp())?;
+    if attachment.filename != filename {
+        return Err(OVTError::AttachmentNotFound.to_resp());
+    }
+
+    let data = state
+        .blobs
+        .get(&attachment.blob_key)
+        .await
+        .map_err(|_| OVTError::AttachmentNotFound.to_resp())?;
+
+    let inline = ["image/", "video/", "audio/"]
+        .iter()
+        .any(|prefix| attachment.content_type.starts_with(prefix));
+    let disposition = if inline {
+        "inline".to_string()
+    } else {
+        format!("attachment; filename=\"{}\"", attachment.filename)
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CACHE_CONTROL, "private, max-age=3600".to_string()),
+        ],
+        data,
+    ))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new().route(
+        "/attachments/:attachment_id/:filename",
+        get(download_attachment),
+    )
+}
@@ -0,0 +1,216 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{channel::Channel, guild::Guild, FromId};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use sqlx::{Postgres, Transaction};
+
+use crate::{
+    attachments::delete_blobs,
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    pubsub::{publish_guild, Event},
+    search::uuid7_floor,
+    state::OVTState,
+    token::get_user,
+};
+
+#[derive(Serialize, Clone)]
+pub struct MessageDeleteBulk {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct BulkDeleteMessages {
+    #[validate(min_items = 1)]
+    #[validate(max_items = 100)]
+    #[validate(unique_items)]
+    messages: Vec<String>,
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct PurgeMessages {
+    author_id: Option<String>,
+    min_date: Option<DateTime<Utc>>,
+    max_date: Option<DateTime<Utc>>,
+    #[validate(min_length = 1)]
+    #[validate(max_length = 256)]
+    contains: Option<String>,
+    /// Newest matching messages are purged first.
+    #[validate(minimum = 1)]
+    #[validate(maximum = 1000)]
+    limit: i64,
+}
+
+impl Default for PurgeMessages {
+    fn default() -> Self {
+        Self {
+            author_id: None,
+            min_date: None,
+            max_date: None,
+            contains: None,
+            limit: 100,
+        }
+    }
+}
+
+async fn authorize(
+    state: &OVTState,
+    headers: &HeaderMap,
+    guild_id: String,
+    channel_id: &str,
+) -> Result<(Guild, Channel), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+
+    Ok((guild, channel))
+}
+
+/// Deletes messages of `channel` inside `tx`, returning the ones which existed.
+async fn delete_messages(
+    state: &OVTState,
+    mut tx: Transaction<'_, Postgres>,
+    guild: Guild,
+    channel: Channel,
+    ids: &[String],
+) -> Result<Json<MessageDeleteBulk>, (StatusCode, Json<ErrorMessage>)> {
+    // attachment rows go with their messages, so their blobs need collecting first
+    let blob_keys = sqlx::query_scalar!(
+        "SELECT blob_key FROM attachments WHERE message_id IN (SELECT id FROM messages WHERE id = ANY($1) AND channel_id = $2);",
+        ids,
+        &channel.id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM messages WHERE id = ANY($1) AND channel_id = $2 RETURNING id;",
+        ids,
+        &channel.id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    delete_blobs(state, blob_keys).await;
+
+    let event = MessageDeleteBulk {
+        guild_id: guild.id,
+        channel_id: channel.id,
+        ids: deleted,
+    };
+    if !event.ids.is_empty() {
+        publish_guild(&event.guild_id, Event::MessageDeleteBulk(event.clone())).await?;
+    }
+
+    Ok(Json(event))
+}
+
+/// Deletes up to 100 messages by id, ids which don't exist in the channel are skipped.
+pub async fn bulk_delete_messages(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<BulkDeleteMessages>,
+) -> Result<Json<MessageDeleteBulk>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (guild, channel) = authorize(&state, &headers, guild_id, &channel_id).await?;
+
+    let tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    delete_messages(&state, tx, guild, channel, &model.messages).await
+}
+
+/// Deletes the newest messages matching every given filter.
+pub async fn purge_messages(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<PurgeMessages>,
+) -> Result<Json<MessageDeleteBulk>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (guild, channel) = authorize(&state, &headers, guild_id, &channel_id).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let ids = sqlx::query_scalar!(
+        r#"SELECT id FROM messages
+        WHERE channel_id = $1
+        AND ($2::TEXT IS NULL OR author_id = $2)
+        AND ($3::TEXT IS NULL OR id COLLATE "C" >= $3)
+        AND ($4::TEXT IS NULL OR id COLLATE "C" < $4)
+        AND ($5::TEXT IS NULL OR strpos(lower(content), lower($5)) > 0)
+        ORDER BY id COLLATE "C" DESC LIMIT $6 FOR UPDATE;"#,
+        &channel.id,
+        model.author_id,
+        model.min_date.map(uuid7_floor),
+        model.max_date.map(uuid7_floor),
+        model.contains,
+        model.limit
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    delete_messages(&state, tx, guild, channel, &ids).await
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/bulk-delete",
+            post(bulk_delete_messages),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/purge",
+            post(purge_messages),
+        )
+}
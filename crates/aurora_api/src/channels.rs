@@ -13,14 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use aurora_db::{channel::Channel, guild::Guild, FromId};
+use std::collections::{HashMap, HashSet};
+
+use aurora_db::{
+    channel::{Channel, ChannelType},
+    channel_follower::ChannelFollower,
+    guild::Guild,
+    FromId,
+};
 use axum::{
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
-    routing::{patch, post},
+    routing::{delete, get, post},
     Json, Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_valid::Validate;
 use sqlx::PgPool;
 
@@ -55,12 +62,81 @@ pub async fn get_channel(
     }
 }
 
+pub async fn get_guild_channels(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Channel>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    let channels = Channel::from_guild(&state.pg, &guild.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(channels))
+}
+
+pub async fn get_guild_channel(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Channel>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+
+    Ok(Json(channel))
+}
+
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// Checks that `parent_id` points at a category in the same guild.
+/// Categories themselves can't be nested.
+pub async fn verify_channel_parent(
+    db: &PgPool,
+    channel_type: ChannelType,
+    parent_id: &str,
+    guild_id: &str,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if channel_type == ChannelType::Category {
+        return Err(OVTError::InvalidChannelParent.to_resp());
+    }
+
+    let parent = get_channel(db, parent_id, guild_id)
+        .await
+        .map_err(|_| OVTError::InvalidChannelParent.to_resp())?;
+
+    if parent.r#type != ChannelType::Category as i32 {
+        return Err(OVTError::InvalidChannelParent.to_resp());
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateGuildChannel {
     #[validate(pattern = r"^[a-b0-9_-]+$")]
     #[validate(min_length = 1)]
     #[validate(max_length = 32)]
     name: String,
+    #[serde(default, rename = "type")]
+    r#type: i32,
+    #[serde(default)]
+    parent_id: Option<String>,
 }
 
 // TODO: foreign servers
@@ -76,12 +152,24 @@ pub async fn create_guild_channel(
         .map_err(|_| OVTError::GuildNotFound.to_resp())?;
     verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
 
+    // threads are created from messages
+    let channel_type = ChannelType::try_from(model.r#type)
+        .ok()
+        .filter(|channel_type| *channel_type != ChannelType::Thread)
+        .ok_or_else(|| OVTError::InvalidChannelType.to_resp())?;
+
+    if let Some(parent_id) = &model.parent_id {
+        verify_channel_parent(&state.pg, channel_type, parent_id, &guild.id).await?;
+    }
+
     let channel = sqlx::query_as!(
         Channel,
-        "INSERT INTO channels (id, name, guild_id, position) VALUES ($1, $2, $3, 0) RETURNING *;",
+        "INSERT INTO channels (id, name, guild_id, position, type, parent_id) VALUES ($1, $2, $3, 0, $4, $5) RETURNING *;",
         uuid7::uuid7().to_string(),
         model.name.trim(),
-        &guild.id
+        &guild.id,
+        channel_type as i32,
+        model.parent_id
     )
     .fetch_one(&state.pg)
     .await
@@ -94,13 +182,17 @@ pub async fn create_guild_channel(
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct ModifyGuildChannel {
+    #[serde(default)]
     #[validate(pattern = r"^[a-b0-9_-]+$")]
     #[validate(min_length = 1)]
     #[validate(max_length = 32)]
-    name: String,
+    name: Option<String>,
+    #[serde(default)]
     #[validate(minimum = 0)]
     #[validate(maximum = 200)]
-    position: u32,
+    position: Option<u32>,
+    #[serde(default, rename = "type")]
+    r#type: Option<i32>,
 }
 
 // TODO: foreign servers
@@ -114,26 +206,165 @@ pub async fn modify_guild_channel(
     let guild = Guild::from_id(&state.pg, guild_id)
         .await
         .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
     verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
 
-    let channel = sqlx::query_as!(
+    // only text and announcement channels can be converted into each other
+    if let Some(new_type) = model.r#type {
+        let convertible = |channel_type| {
+            matches!(
+                ChannelType::try_from(channel_type),
+                Ok(ChannelType::Text | ChannelType::Announcement)
+            )
+        };
+
+        if !convertible(new_type) || !convertible(channel.r#type) {
+            return Err(OVTError::InvalidChannelType.to_resp());
+        }
+    }
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let modified_channel = sqlx::query_as!(
         Channel,
-        "UPDATE channels SET name = $1, position = $2 WHERE id = $3 AND guild_id = $4 RETURNING *;",
-        &model.name.trim(),
-        model.position as i32,
-        &channel_id,
-        &guild.id
+        "UPDATE channels SET name = COALESCE($1, name), position = COALESCE($2, position), type = COALESCE($3, type) WHERE id = $4 RETURNING *;",
+        model.name.as_deref().map(str::trim),
+        model.position.map(|position| position as i32),
+        model.r#type,
+        &channel.id
     )
-    .fetch_optional(&state.pg)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| OVTError::InternalServerError.to_resp())?;
 
-    if let Some(modified_channel) = channel {
-        publish_guild(&guild.id, Event::ChannelModified(modified_channel.clone())).await?;
-        Ok(Json(modified_channel))
-    } else {
-        Err(OVTError::ChannelNotFound.to_resp())
+    if modified_channel.r#type != ChannelType::Announcement as i32 {
+        sqlx::query!(
+            "DELETE FROM channel_followers WHERE channel_id = $1;",
+            &channel.id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ChannelModified(modified_channel.clone())).await?;
+
+    Ok(Json(modified_channel))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ModifyGuildChannelPosition {
+    id: String,
+    #[validate(minimum = 0)]
+    #[validate(maximum = 200)]
+    position: u32,
+    /// `null` moves the channel out of its category, leaving it out keeps the current parent.
+    #[serde(default, deserialize_with = "double_option")]
+    parent_id: Option<Option<String>>,
+}
+
+pub async fn modify_guild_channel_positions(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+    Json(model): Json<Vec<ModifyGuildChannelPosition>>,
+) -> Result<Json<Vec<Channel>>, (StatusCode, Json<ErrorMessage>)> {
+    if model.is_empty() || model.len() > 200 || model.validate().is_err() {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+
+    let mut seen = HashSet::new();
+    if !model.iter().all(|pos| seen.insert(&pos.id)) {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let mut channels: HashMap<String, Channel> = sqlx::query_as!(
+        Channel,
+        "SELECT * FROM channels WHERE guild_id = $1 FOR UPDATE;",
+        &guild.id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .into_iter()
+    .map(|channel| (channel.id.clone(), channel))
+    .collect();
+
+    for pos in &model {
+        let channel = channels
+            .get_mut(&pos.id)
+            .ok_or_else(|| OVTError::ChannelNotFound.to_resp())?;
+        if channel.r#type == ChannelType::Thread as i32 {
+            return Err(OVTError::InvalidChannelType.to_resp());
+        }
+        channel.position = pos.position as i32;
+        if let Some(parent_id) = &pos.parent_id {
+            channel.parent_id.clone_from(parent_id);
+        }
+    }
+
+    // verify the resulting tree, since parents may have moved in the same request
+    for pos in &model {
+        let channel = &channels[&pos.id];
+        if let Some(parent_id) = &channel.parent_id {
+            let parent_is_category = channels
+                .get(parent_id)
+                .is_some_and(|parent| parent.r#type == ChannelType::Category as i32);
+
+            if channel.r#type == ChannelType::Category as i32 || !parent_is_category {
+                return Err(OVTError::InvalidChannelParent.to_resp());
+            }
+        }
+    }
+
+    let mut modified_channels = Vec::with_capacity(model.len());
+    for pos in &model {
+        let channel = &channels[&pos.id];
+        modified_channels.push(
+            sqlx::query_as!(
+                Channel,
+                "UPDATE channels SET position = $2, parent_id = $3 WHERE id = $1 RETURNING *;",
+                &channel.id,
+                channel.position,
+                channel.parent_id
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|_| OVTError::InternalServerError.to_resp())?,
+        );
     }
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(
+        &guild.id,
+        Event::ChannelsReordered(modified_channels.clone()),
+    )
+    .await?;
+
+    Ok(Json(modified_channels))
 }
 
 pub async fn delete_guild_channel(
@@ -148,25 +379,191 @@ pub async fn delete_guild_channel(
     get_channel(&state.pg, &channel_id, &guild.id).await?;
     verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
 
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // threads would otherwise be left without a parent, where nobody can reach them
+    let threads = sqlx::query!(
+        "DELETE FROM channels WHERE parent_id = $1 AND type = $2 RETURNING id;",
+        &channel_id,
+        ChannelType::Thread as i32
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
     sqlx::query!(
         "DELETE FROM channels WHERE id = $1 AND guild_id = $2;",
         &channel_id,
         &guild.id
     )
-    .execute(&state.pg)
+    .execute(&mut *tx)
     .await
     .map_err(|_| OVTError::InternalServerError.to_resp())?;
 
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    for thread in threads {
+        publish_guild(&guild.id, Event::ChannelDelete(thread.id)).await?;
+    }
     publish_guild(&guild.id, Event::ChannelDelete(channel_id)).await?;
 
     Ok((StatusCode::NO_CONTENT, "".to_string()))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FollowGuildChannel {
+    channel_id: String,
+}
+
+/// Follows an announcement channel into a text channel of a guild the user manages.
+pub async fn follow_guild_channel(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<FollowGuildChannel>,
+) -> Result<Json<ChannelFollower>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    if channel.r#type != ChannelType::Announcement as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let target_channel = Channel::from_id(&state.pg, model.channel_id)
+        .await
+        .map_err(|_| OVTError::ChannelNotFound.to_resp())?;
+    let target_guild = Guild::from_id(
+        &state.pg,
+        target_channel
+            .guild_id
+            .clone()
+            .ok_or_else(|| OVTError::ChannelNotFound.to_resp())?,
+    )
+    .await
+    .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &target_guild,
+        GuildPermissions::MANAGE_CHANNELS,
+    )
+    .await?;
+
+    if target_channel.r#type != ChannelType::Text as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let follower = sqlx::query_as!(
+        ChannelFollower,
+        "INSERT INTO channel_followers (channel_id, target_guild_id, target_channel_id) VALUES ($1, $2, $3) ON CONFLICT (channel_id, target_channel_id) DO UPDATE SET target_guild_id = EXCLUDED.target_guild_id RETURNING *;",
+        &channel.id,
+        &target_guild.id,
+        &target_channel.id
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(follower))
+}
+
+pub async fn get_guild_channel_followers(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ChannelFollower>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
+
+    let followers = ChannelFollower::from_channel(&state.pg, &channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(followers))
+}
+
+/// Either side of a follow may remove it.
+pub async fn unfollow_guild_channel(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, target_channel_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    let follower = ChannelFollower::from_id(&state.pg, (&channel.id, &target_channel_id))
+        .await
+        .map_err(|_| OVTError::FollowerNotFound.to_resp())?;
+
+    if verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS)
+        .await
+        .is_err()
+    {
+        let target_guild = Guild::from_id(&state.pg, follower.target_guild_id.clone())
+            .await
+            .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+        verify_permissions(
+            &state.pg,
+            &actor,
+            &target_guild,
+            GuildPermissions::MANAGE_CHANNELS,
+        )
+        .await?;
+    }
+
+    sqlx::query!(
+        "DELETE FROM channel_followers WHERE channel_id = $1 AND target_channel_id = $2;",
+        &follower.channel_id,
+        &follower.target_channel_id
+    )
+    .execute(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
 pub fn router() -> Router<OVTState> {
     Router::<OVTState>::new()
-        .route("/guilds/:guild_id/channels", post(create_guild_channel))
+        .route(
+            "/guilds/:guild_id/channels",
+            post(create_guild_channel)
+                .get(get_guild_channels)
+                .patch(modify_guild_channel_positions),
+        )
         .route(
             "/guilds/:guild_id/channels/:channel_id",
-            patch(modify_guild_channel).delete(delete_guild_channel),
+            get(get_guild_channel)
+                .patch(modify_guild_channel)
+                .delete(delete_guild_channel),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/followers",
+            post(follow_guild_channel).get(get_guild_channel_followers),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/followers/:target_channel_id",
+            delete(unfollow_guild_channel),
         )
 }
@@ -0,0 +1,241 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use aurora_db::application::{CommandChoice, CommandOption, CommandOptionType, CommandValue};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_COMMAND_OPTIONS: usize = 25;
+pub const MAX_OPTION_CHOICES: usize = 25;
+pub const MAX_STRING_OPTION_LENGTH: usize = 2048;
+
+#[derive(Debug, PartialEq)]
+pub enum CommandError {
+    InvalidName(String),
+    InvalidDescription(String),
+    TooManyOptions,
+    DuplicateOption(String),
+    /// Required options have to come before optional ones.
+    RequiredAfterOptional(String),
+    /// Choices, ranges or lengths which don't fit the option's type, or each other.
+    InvalidConstraint(String),
+    UnknownOption(String),
+    MissingOption(String),
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid name {name:?}"),
+            Self::InvalidDescription(name) => write!(f, "invalid description for {name:?}"),
+            Self::TooManyOptions => write!(f, "too many options"),
+            Self::DuplicateOption(name) => write!(f, "option {name:?} is given twice"),
+            Self::RequiredAfterOptional(name) => {
+                write!(f, "required option {name:?} comes after an optional one")
+            }
+            Self::InvalidConstraint(name) => write!(f, "invalid constraints on option {name:?}"),
+            Self::UnknownOption(name) => write!(f, "unknown option {name:?}"),
+            Self::MissingOption(name) => write!(f, "missing required option {name:?}"),
+            Self::InvalidValue(name) => write!(f, "invalid value for option {name:?}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Command and option names are lowercase, like `/remind-me`.
+fn is_valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || matches!(byte, b'-' | b'_')
+        })
+}
+
+fn is_valid_description(description: &str) -> bool {
+    (1..=100).contains(&description.chars().count())
+}
+
+/// Whether `value` can be given to an option of type `option_type`, before any constraints.
+fn fits_type(option_type: CommandOptionType, value: &CommandValue) -> bool {
+    matches!(
+        (option_type, value),
+        (
+            CommandOptionType::String | CommandOptionType::User | CommandOptionType::Channel,
+            CommandValue::String(_)
+        ) | (CommandOptionType::Integer, CommandValue::Integer(_))
+            | (
+                CommandOptionType::Number,
+                CommandValue::Integer(_) | CommandValue::Number(_)
+            )
+            | (CommandOptionType::Boolean, CommandValue::Boolean(_))
+    )
+}
+
+fn verify_option(option: &CommandOption) -> Result<(), CommandError> {
+    let invalid = || CommandError::InvalidConstraint(option.name.clone());
+    let is_numeric = matches!(
+        option.r#type,
+        CommandOptionType::Integer | CommandOptionType::Number
+    );
+    let is_string = option.r#type == CommandOptionType::String;
+
+    if !option.choices.is_empty() {
+        if !(is_numeric || is_string) || option.choices.len() > MAX_OPTION_CHOICES {
+            return Err(invalid());
+        }
+        for CommandChoice { name, value } in &option.choices {
+            if !is_valid_description(name) || !fits_type(option.r#type, value) {
+                return Err(invalid());
+            }
+        }
+    }
+
+    if (option.min_value.is_some() || option.max_value.is_some()) && !is_numeric {
+        return Err(invalid());
+    }
+    if let (Some(min), Some(max)) = (option.min_value, option.max_value) {
+        if min > max {
+            return Err(invalid());
+        }
+    }
+
+    if (option.min_length.is_some() || option.max_length.is_some()) && !is_string {
+        return Err(invalid());
+    }
+    if option.max_length.unwrap_or(MAX_STRING_OPTION_LENGTH) > MAX_STRING_OPTION_LENGTH
+        || option.min_length.unwrap_or(0) > option.max_length.unwrap_or(MAX_STRING_OPTION_LENGTH)
+    {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+/// Checks a command definition before it gets registered.
+pub fn verify_command(
+    name: &str,
+    description: &str,
+    options: &[CommandOption],
+) -> Result<(), CommandError> {
+    if !is_valid_name(name) {
+        return Err(CommandError::InvalidName(name.to_string()));
+    }
+    if !is_valid_description(description) {
+        return Err(CommandError::InvalidDescription(name.to_string()));
+    }
+    if options.len() > MAX_COMMAND_OPTIONS {
+        return Err(CommandError::TooManyOptions);
+    }
+
+    let mut names = HashSet::new();
+    let mut seen_optional = false;
+    for option in options {
+        if !is_valid_name(&option.name) {
+            return Err(CommandError::InvalidName(option.name.clone()));
+        }
+        if !is_valid_description(&option.description) {
+            return Err(CommandError::InvalidDescription(option.name.clone()));
+        }
+        if !names.insert(option.name.as_str()) {
+            return Err(CommandError::DuplicateOption(option.name.clone()));
+        }
+        if option.required && seen_optional {
+            return Err(CommandError::RequiredAfterOptional(option.name.clone()));
+        }
+        seen_optional |= !option.required;
+
+        verify_option(option)?;
+    }
+
+    Ok(())
+}
+
+/// An option value given by the user invoking a command.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct OptionValue {
+    pub name: String,
+    pub value: CommandValue,
+}
+
+fn verify_value(option: &CommandOption, value: &CommandValue) -> bool {
+    if !fits_type(option.r#type, value) {
+        return false;
+    }
+    if !option.choices.is_empty() && !option.choices.iter().any(|choice| choice.value == *value) {
+        return false;
+    }
+
+    match value {
+        CommandValue::Integer(number) => in_range(option, *number as f64),
+        CommandValue::Number(number) => number.is_finite() && in_range(option, *number),
+        CommandValue::String(string) => {
+            let length = string.chars().count();
+            length >= option.min_length.unwrap_or(0)
+                && length <= option.max_length.unwrap_or(MAX_STRING_OPTION_LENGTH)
+        }
+        CommandValue::Boolean(_) => true,
+    }
+}
+
+fn in_range(option: &CommandOption, number: f64) -> bool {
+    option.min_value.is_none_or(|min| number >= min)
+        && option.max_value.is_none_or(|max| number <= max)
+}
+
+/// Checks the values given when invoking a command against its options, returning them in the
+/// command's order with numbers normalized to the option's type.
+pub fn resolve_options(
+    options: &[CommandOption],
+    values: Vec<OptionValue>,
+) -> Result<Vec<OptionValue>, CommandError> {
+    let mut names = HashSet::new();
+    for value in &values {
+        if !options.iter().any(|option| option.name == value.name) {
+            return Err(CommandError::UnknownOption(value.name.clone()));
+        }
+        if !names.insert(value.name.as_str()) {
+            return Err(CommandError::DuplicateOption(value.name.clone()));
+        }
+    }
+
+    let mut resolved = Vec::with_capacity(values.len());
+    for option in options {
+        let Some(value) = values.iter().find(|value| value.name == option.name) else {
+            if option.required {
+                return Err(CommandError::MissingOption(option.name.clone()));
+            }
+            continue;
+        };
+
+        if !verify_value(option, &value.value) {
+            return Err(CommandError::InvalidValue(option.name.clone()));
+        }
+
+        let value = match (option.r#type, &value.value) {
+            (CommandOptionType::Number, CommandValue::Integer(number)) => {
+                CommandValue::Number(*number as f64)
+            }
+            (_, value) => value.clone(),
+        };
+        resolved.push(OptionValue {
+            name: option.name.clone(),
+            value,
+        });
+    }
+
+    Ok(resolved)
+}
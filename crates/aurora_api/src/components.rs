@@ -0,0 +1,243 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use aurora_db::component::{ActionRow, Button, ButtonStyle, Component, SelectMenu};
+use serde_json::Value;
+
+use crate::net::parse_web_url;
+
+pub const MAX_ACTION_ROWS: usize = 5;
+pub const MAX_ROW_COMPONENTS: usize = 5;
+pub const MAX_SELECT_OPTIONS: usize = 25;
+
+#[derive(Debug, PartialEq)]
+pub enum ComponentError {
+    TooManyRows,
+    /// Only action rows can be at the top level, and they can't be nested.
+    InvalidLayout,
+    /// A row which is empty, too full, or mixes a select menu with anything else.
+    InvalidRow,
+    InvalidCustomId(String),
+    DuplicateCustomId(String),
+    InvalidButton,
+    InvalidSelectMenu(String),
+    UnknownComponent(String),
+    /// The component can't be used, being disabled or a link.
+    Unusable(String),
+    InvalidValues(String),
+}
+
+impl std::fmt::Display for ComponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyRows => write!(f, "too many action rows"),
+            Self::InvalidLayout => write!(f, "components have to be laid out in action rows"),
+            Self::InvalidRow => write!(f, "invalid action row"),
+            Self::InvalidCustomId(id) => write!(f, "invalid custom id {id:?}"),
+            Self::DuplicateCustomId(id) => write!(f, "custom id {id:?} is used twice"),
+            Self::InvalidButton => write!(f, "invalid button"),
+            Self::InvalidSelectMenu(id) => write!(f, "invalid select menu {id:?}"),
+            Self::UnknownComponent(id) => write!(f, "no component with custom id {id:?}"),
+            Self::Unusable(id) => write!(f, "component {id:?} can't be used"),
+            Self::InvalidValues(id) => write!(f, "invalid values for {id:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ComponentError {}
+
+fn length_within(string: &str, max: usize) -> bool {
+    (1..=max).contains(&string.chars().count())
+}
+
+fn verify_custom_id<'a>(
+    custom_id: &'a str,
+    custom_ids: &mut HashSet<&'a str>,
+) -> Result<(), ComponentError> {
+    if !length_within(custom_id, 100) {
+        return Err(ComponentError::InvalidCustomId(custom_id.to_string()));
+    }
+    if !custom_ids.insert(custom_id) {
+        return Err(ComponentError::DuplicateCustomId(custom_id.to_string()));
+    }
+
+    Ok(())
+}
+
+fn verify_button<'a>(
+    button: &'a Button,
+    custom_ids: &mut HashSet<&'a str>,
+) -> Result<(), ComponentError> {
+    if !length_within(&button.label, 80) {
+        return Err(ComponentError::InvalidButton);
+    }
+
+    // link buttons open their url, every other style sends an interaction with its custom id
+    match (button.style, &button.custom_id, &button.url) {
+        (ButtonStyle::Link, None, Some(url)) => parse_web_url(url)
+            .map(|_| ())
+            .ok_or(ComponentError::InvalidButton),
+        (ButtonStyle::Link, _, _) | (_, None, _) | (_, _, Some(_)) => {
+            Err(ComponentError::InvalidButton)
+        }
+        (_, Some(custom_id), None) => verify_custom_id(custom_id, custom_ids),
+    }
+}
+
+fn verify_select_menu<'a>(
+    menu: &'a SelectMenu,
+    custom_ids: &mut HashSet<&'a str>,
+) -> Result<(), ComponentError> {
+    verify_custom_id(&menu.custom_id, custom_ids)?;
+    let invalid = || ComponentError::InvalidSelectMenu(menu.custom_id.clone());
+
+    if menu.options.is_empty() || menu.options.len() > MAX_SELECT_OPTIONS {
+        return Err(invalid());
+    }
+    if menu
+        .placeholder
+        .as_ref()
+        .is_some_and(|placeholder| !length_within(placeholder, 150))
+    {
+        return Err(invalid());
+    }
+    if menu.max_values == 0 || menu.min_values > menu.max_values {
+        return Err(invalid());
+    }
+    if menu.max_values > menu.options.len() {
+        return Err(invalid());
+    }
+
+    let mut values = HashSet::new();
+    for option in &menu.options {
+        if !length_within(&option.label, 100)
+            || !length_within(&option.value, 100)
+            || option
+                .description
+                .as_ref()
+                .is_some_and(|description| !length_within(description, 100))
+            || !values.insert(option.value.as_str())
+        {
+            return Err(invalid());
+        }
+    }
+    if menu.options.iter().filter(|option| option.default).count() > menu.max_values {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+fn verify_row<'a>(
+    row: &'a ActionRow,
+    custom_ids: &mut HashSet<&'a str>,
+) -> Result<(), ComponentError> {
+    if row.components.is_empty() || row.components.len() > MAX_ROW_COMPONENTS {
+        return Err(ComponentError::InvalidRow);
+    }
+    // a select menu takes up a whole row
+    if row.components.len() > 1
+        && row
+            .components
+            .iter()
+            .any(|component| matches!(component, Component::StringSelect(_)))
+    {
+        return Err(ComponentError::InvalidRow);
+    }
+
+    for component in &row.components {
+        match component {
+            Component::ActionRow(_) => return Err(ComponentError::InvalidLayout),
+            Component::Button(button) => verify_button(button, custom_ids)?,
+            Component::StringSelect(menu) => verify_select_menu(menu, custom_ids)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a message's components before it gets sent.
+pub fn verify_components(components: &[Component]) -> Result<(), ComponentError> {
+    if components.len() > MAX_ACTION_ROWS {
+        return Err(ComponentError::TooManyRows);
+    }
+
+    let mut custom_ids = HashSet::new();
+    for component in components {
+        let Component::ActionRow(row) = component else {
+            return Err(ComponentError::InvalidLayout);
+        };
+        verify_row(row, &mut custom_ids)?;
+    }
+
+    Ok(())
+}
+
+/// The form components are stored in on messages, where having none is `NULL`.
+pub fn stored_components(components: &[Component]) -> Option<Value> {
+    if components.is_empty() {
+        return None;
+    }
+
+    serde_json::to_value(components).ok()
+}
+
+fn find_component<'a>(components: &'a [Component], custom_id: &str) -> Option<&'a Component> {
+    components.iter().find_map(|component| match component {
+        Component::ActionRow(row) => find_component(&row.components, custom_id),
+        Component::Button(button) => {
+            (button.custom_id.as_deref() == Some(custom_id)).then_some(component)
+        }
+        Component::StringSelect(menu) => (menu.custom_id == custom_id).then_some(component),
+    })
+}
+
+/// Checks that the component with `custom_id` can be used with `values`, which are only given
+/// for select menus.
+pub fn verify_component_use(
+    components: &[Component],
+    custom_id: &str,
+    values: &[String],
+) -> Result<(), ComponentError> {
+    let component = find_component(components, custom_id)
+        .ok_or_else(|| ComponentError::UnknownComponent(custom_id.to_string()))?;
+    let invalid_values = || ComponentError::InvalidValues(custom_id.to_string());
+
+    match component {
+        Component::Button(button) if !button.disabled => {
+            if !values.is_empty() {
+                return Err(invalid_values());
+            }
+        }
+        Component::StringSelect(menu) if !menu.disabled => {
+            if values.len() < menu.min_values || values.len() > menu.max_values {
+                return Err(invalid_values());
+            }
+            let mut picked = HashSet::new();
+            for value in values {
+                if !menu.options.iter().any(|option| option.value == *value)
+                    || !picked.insert(value)
+                {
+                    return Err(invalid_values());
+                }
+            }
+        }
+        _ => return Err(ComponentError::Unusable(custom_id.to_string())),
+    }
+
+    Ok(())
+}
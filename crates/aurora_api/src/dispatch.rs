@@ -0,0 +1,275 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::sync::{mpsc::UnboundedReceiver, Notify};
+
+use crate::net::pinned_client;
+
+/// Deliveries fail for good after this many attempts.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+/// Subscriptions are disabled after this many failed attempts in a row.
+pub const MAX_CONSECUTIVE_FAILURES: i32 = 15;
+
+/// Delay before the first retry, each later one waits four times longer.
+const RETRY_BACKOFF_SECS: i64 = 30;
+/// How long a delivery is claimed for while it's being attempted.
+const DELIVERY_LEASE_SECS: f64 = 60.0;
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+/// Limits on where deliveries are sent.
+#[derive(Clone)]
+pub struct DeliveryConfig {
+    /// Covers a whole attempt, from resolving the subscription's host to its response.
+    pub timeout: Duration,
+    /// Only for tests against local servers, deliveries must never reach internal services.
+    pub allow_private_networks: bool,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            allow_private_networks: false,
+        }
+    }
+}
+
+/// An event published to a guild, already serialized.
+pub struct GuildEvent {
+    pub guild_id: String,
+    pub event_type: String,
+    pub payload: String,
+}
+
+/// The `x-aurora-signature` of a delivery, HMAC-SHA256 over `{timestamp}.{payload}`.
+pub fn sign_delivery(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(format!("{timestamp}.{payload}").as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Queues a delivery of `event` for every enabled subscription to it, returning how many were queued.
+pub async fn enqueue(db: &PgPool, event: &GuildEvent) -> Result<usize, sqlx::Error> {
+    let subscription_ids = sqlx::query_scalar!(
+        "SELECT id FROM event_subscriptions WHERE guild_id = $1 AND enabled AND $2 = ANY(event_types);",
+        &event.guild_id,
+        &event.event_type
+    )
+    .fetch_all(db)
+    .await?;
+
+    for subscription_id in &subscription_ids {
+        sqlx::query!(
+            "INSERT INTO event_deliveries (id, subscription_id, event_type, payload) VALUES ($1, $2, $3, $4);",
+            uuid7::uuid7().to_string(),
+            subscription_id,
+            &event.event_type,
+            &event.payload
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(subscription_ids.len())
+}
+
+struct ClaimedDelivery {
+    id: String,
+    subscription_id: String,
+    event_type: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Attempts every delivery which is due, returning how many were attempted.
+pub async fn deliver_due(db: &PgPool, config: &DeliveryConfig) -> Result<usize, sqlx::Error> {
+    // claiming pushes next_attempt_at forward, so a crash mid-attempt just retries later
+    let claimed = sqlx::query_as!(
+        ClaimedDelivery,
+        r#"WITH claimed AS (
+            UPDATE event_deliveries SET next_attempt_at = now() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT delivery.id FROM event_deliveries delivery
+                JOIN event_subscriptions subscription ON subscription.id = delivery.subscription_id
+                WHERE delivery.status = 'pending' AND delivery.next_attempt_at <= now() AND subscription.enabled
+                ORDER BY delivery.next_attempt_at LIMIT $1
+                FOR UPDATE OF delivery SKIP LOCKED
+            )
+            RETURNING *
+        )
+        SELECT claimed.id, claimed.subscription_id, claimed.event_type, claimed.payload, claimed.attempts, subscription.url, subscription.secret
+        FROM claimed JOIN event_subscriptions subscription ON subscription.id = claimed.subscription_id;"#,
+        DELIVERY_BATCH_SIZE,
+        DELIVERY_LEASE_SECS
+    )
+    .fetch_all(db)
+    .await?;
+
+    let attempted = claimed.len();
+    for result in join_all(claimed.iter().map(|delivery| attempt(db, config, delivery))).await {
+        result?;
+    }
+
+    Ok(attempted)
+}
+
+async fn attempt(
+    db: &PgPool,
+    config: &DeliveryConfig,
+    delivery: &ClaimedDelivery,
+) -> Result<(), sqlx::Error> {
+    let timestamp = Utc::now().timestamp();
+    // the host is checked on every attempt, it may have started resolving somewhere internal
+    let request = async {
+        let url = Url::parse(&delivery.url).map_err(|err| err.to_string())?;
+        let client = pinned_client(&url, config.allow_private_networks)
+            .await
+            .ok_or_else(|| "url doesn't resolve to a public address".to_string())?
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        client
+            .post(url)
+            .header("content-type", "application/json")
+            .header("x-aurora-event", &delivery.event_type)
+            .header("x-aurora-delivery", &delivery.id)
+            .header("x-aurora-timestamp", timestamp)
+            .header(
+                "x-aurora-signature",
+                sign_delivery(&delivery.secret, timestamp, &delivery.payload),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|err| err.to_string())
+    };
+    let response = tokio::time::timeout(config.timeout, request)
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()));
+
+    let (status_code, error) = match response {
+        Ok(response) if response.status().is_success() => {
+            sqlx::query!(
+                "UPDATE event_deliveries SET status = 'succeeded', attempts = attempts + 1, last_status_code = $2, last_error = NULL, delivered_at = now() WHERE id = $1;",
+                &delivery.id,
+                response.status().as_u16() as i32
+            )
+            .execute(db)
+            .await?;
+            sqlx::query!(
+                "UPDATE event_subscriptions SET consecutive_failures = 0 WHERE id = $1;",
+                &delivery.subscription_id
+            )
+            .execute(db)
+            .await?;
+
+            return Ok(());
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            format!("received {}", response.status()),
+        ),
+        Err(error) => (None, error),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let status = if attempts >= MAX_DELIVERY_ATTEMPTS {
+        "failed"
+    } else {
+        "pending"
+    };
+    let backoff = RETRY_BACKOFF_SECS * 4i64.pow(attempts as u32 - 1);
+    sqlx::query!(
+        "UPDATE event_deliveries SET status = $2, attempts = $3, last_status_code = $4, last_error = $5, next_attempt_at = now() + make_interval(secs => $6) WHERE id = $1;",
+        &delivery.id,
+        status,
+        attempts,
+        status_code,
+        error.chars().take(512).collect::<String>(),
+        backoff as f64
+    )
+    .execute(db)
+    .await?;
+
+    let disabled = sqlx::query_scalar!(
+        "UPDATE event_subscriptions SET consecutive_failures = consecutive_failures + 1, enabled = enabled AND consecutive_failures + 1 < $2 WHERE id = $1 RETURNING NOT enabled AS \"disabled!\";",
+        &delivery.subscription_id,
+        MAX_CONSECUTIVE_FAILURES
+    )
+    .fetch_one(db)
+    .await?;
+
+    if disabled {
+        sqlx::query!(
+            "UPDATE event_deliveries SET status = 'failed', last_error = 'subscription disabled' WHERE subscription_id = $1 AND status = 'pending';",
+            &delivery.subscription_id
+        )
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Queues events as they're published and keeps delivering them in the background.
+pub async fn run_dispatcher(
+    db: PgPool,
+    config: DeliveryConfig,
+    mut events: UnboundedReceiver<GuildEvent>,
+) {
+    let queued = Arc::new(Notify::new());
+
+    tokio::spawn({
+        let db = db.clone();
+        let queued = queued.clone();
+
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = queued.notified() => {}
+                }
+
+                // keep going while full batches come back, there may be more due
+                while let Ok(attempted) = deliver_due(&db, &config).await {
+                    if attempted < DELIVERY_BATCH_SIZE as usize {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    while let Some(event) = events.recv().await {
+        if let Ok(queued_deliveries) = enqueue(&db, &event).await {
+            if queued_deliveries > 0 {
+                queued.notify_one();
+            }
+        }
+    }
+}
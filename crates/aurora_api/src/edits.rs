@@ -0,0 +1,25 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::Deserialize;
+use serde_valid::Validate;
+
+/// An edit to a message, which has to leave it with valid content.
+#[derive(Deserialize, Validate)]
+pub struct ModifyMessage {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 2048)]
+    pub content: String,
+}
@@ -0,0 +1,166 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::embed::Embed;
+use reqwest::Url;
+use serde_json::Value;
+
+pub const MAX_EMBEDS: usize = 10;
+pub const MAX_EMBED_FIELDS: usize = 25;
+pub const MAX_TITLE_LENGTH: usize = 256;
+pub const MAX_DESCRIPTION_LENGTH: usize = 4096;
+pub const MAX_FIELD_NAME_LENGTH: usize = 256;
+pub const MAX_FIELD_VALUE_LENGTH: usize = 1024;
+pub const MAX_FOOTER_LENGTH: usize = 2048;
+pub const MAX_AUTHOR_LENGTH: usize = 256;
+/// Characters allowed across every text in every embed of a message.
+pub const MAX_TOTAL_LENGTH: usize = 6000;
+pub const MAX_EMBED_URL_LENGTH: usize = 2048;
+
+#[derive(Debug, PartialEq)]
+pub enum EmbedError {
+    TooManyEmbeds,
+    /// An embed with nothing to show.
+    Empty,
+    TooManyFields,
+    /// A text which is empty or too long, named by its path like `fields.2.value`.
+    InvalidText(String),
+    InvalidUrl(String),
+    InvalidColor,
+    TooLong,
+}
+
+impl std::fmt::Display for EmbedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyEmbeds => write!(f, "too many embeds"),
+            Self::Empty => write!(f, "embed is empty"),
+            Self::TooManyFields => write!(f, "too many fields"),
+            Self::InvalidText(path) => write!(f, "invalid {path}"),
+            Self::InvalidUrl(path) => write!(f, "invalid url in {path}"),
+            Self::InvalidColor => write!(f, "invalid color"),
+            Self::TooLong => write!(f, "embeds are too long in total"),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {}
+
+/// Checks a required text, returning its length in characters.
+fn text_length(text: &str, max: usize, path: &str) -> Result<usize, EmbedError> {
+    let length = text.chars().count();
+    if text.trim().is_empty() || length > max {
+        return Err(EmbedError::InvalidText(path.to_string()));
+    }
+
+    Ok(length)
+}
+
+fn optional_text_length(text: Option<&str>, max: usize, path: &str) -> Result<usize, EmbedError> {
+    text.map_or(Ok(0), |text| text_length(text, max, path))
+}
+
+fn verify_url(url: Option<&str>, path: &str) -> Result<(), EmbedError> {
+    let Some(url) = url else {
+        return Ok(());
+    };
+
+    match Url::parse(url) {
+        Ok(parsed)
+            if url.len() <= MAX_EMBED_URL_LENGTH && matches!(parsed.scheme(), "http" | "https") =>
+        {
+            Ok(())
+        }
+        _ => Err(EmbedError::InvalidUrl(path.to_string())),
+    }
+}
+
+/// Checks a single embed, returning how many characters of text it has.
+fn verify_embed(embed: &Embed) -> Result<usize, EmbedError> {
+    if *embed == Embed::default() {
+        return Err(EmbedError::Empty);
+    }
+    if embed.fields.len() > MAX_EMBED_FIELDS {
+        return Err(EmbedError::TooManyFields);
+    }
+    if embed.color.is_some_and(|color| color > 0xffffff) {
+        return Err(EmbedError::InvalidColor);
+    }
+
+    let mut length = optional_text_length(embed.title.as_deref(), MAX_TITLE_LENGTH, "title")?
+        + optional_text_length(
+            embed.description.as_deref(),
+            MAX_DESCRIPTION_LENGTH,
+            "description",
+        )?;
+    verify_url(embed.url.as_deref(), "url")?;
+
+    for (i, field) in embed.fields.iter().enumerate() {
+        length += text_length(
+            &field.name,
+            MAX_FIELD_NAME_LENGTH,
+            &format!("fields.{i}.name"),
+        )?;
+        length += text_length(
+            &field.value,
+            MAX_FIELD_VALUE_LENGTH,
+            &format!("fields.{i}.value"),
+        )?;
+    }
+
+    if let Some(footer) = &embed.footer {
+        length += text_length(&footer.text, MAX_FOOTER_LENGTH, "footer.text")?;
+        verify_url(footer.icon_url.as_deref(), "footer.icon_url")?;
+    }
+    if let Some(author) = &embed.author {
+        length += text_length(&author.name, MAX_AUTHOR_LENGTH, "author.name")?;
+        verify_url(author.url.as_deref(), "author.url")?;
+        verify_url(author.icon_url.as_deref(), "author.icon_url")?;
+    }
+    if let Some(image) = &embed.image {
+        verify_url(Some(&image.url), "image.url")?;
+    }
+    if let Some(thumbnail) = &embed.thumbnail {
+        verify_url(Some(&thumbnail.url), "thumbnail.url")?;
+    }
+
+    Ok(length)
+}
+
+/// Checks a message's embeds before it gets sent.
+pub fn verify_embeds(embeds: &[Embed]) -> Result<(), EmbedError> {
+    if embeds.len() > MAX_EMBEDS {
+        return Err(EmbedError::TooManyEmbeds);
+    }
+
+    let mut length = 0;
+    for embed in embeds {
+        length += verify_embed(embed)?;
+    }
+    if length > MAX_TOTAL_LENGTH {
+        return Err(EmbedError::TooLong);
+    }
+
+    Ok(())
+}
+
+/// The form embeds are stored in on messages, where having none is `NULL`.
+pub fn stored_embeds(embeds: &[Embed]) -> Option<Value> {
+    if embeds.is_empty() {
+        return None;
+    }
+
+    serde_json::to_value(embeds).ok()
+}
@@ -36,6 +36,45 @@ pub enum OVTError {
     GuildAlreadyJoined,
     InviteNotFound,
     InvalidPermissionBitflags,
+    InvalidQuery,
+    InvalidBody,
+    InvalidChannelParent,
+    InvalidChannelType,
+    MessageAlreadyCrossposted,
+    FollowerNotFound,
+    ThreadNotFound,
+    ThreadArchived,
+    ThreadAlreadyExists,
+    TagNotFound,
+    MaximumTagsReached,
+    InvalidEmoji,
+    MaximumReactionsReached,
+    InvalidMessageReference,
+    MaximumPinsReached,
+    InvalidAttachmentType,
+    AttachmentTooLarge,
+    MaximumAttachmentsReached,
+    InvalidSignature,
+    AttachmentNotFound,
+    InvalidImage,
+    AssetNotFound,
+    RateLimited,
+    WebhookNotFound,
+    SubscriptionNotFound,
+    MaximumSubscriptionsReached,
+    ApplicationNotFound,
+    CommandNotFound,
+    InvalidCommand,
+    MaximumCommandsReached,
+    InteractionNotFound,
+    InteractionAlreadyResponded,
+    ApplicationUnavailable,
+    InvalidComponents,
+    ComponentNotFound,
+    InvalidEmbeds,
+    PollNotFound,
+    PollAnswerNotFound,
+    PollClosed,
 }
 
 impl OVTError {
@@ -125,6 +164,279 @@ impl OVTError {
                     code: 11,
                 }),
             ),
+            Self::InvalidQuery => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid query parameters".to_string(),
+                    code: 12,
+                }),
+            ),
+            Self::InvalidBody => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid request body".to_string(),
+                    code: 13,
+                }),
+            ),
+            Self::InvalidChannelParent => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid parent channel".to_string(),
+                    code: 14,
+                }),
+            ),
+            Self::InvalidChannelType => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid channel type".to_string(),
+                    code: 15,
+                }),
+            ),
+            Self::MessageAlreadyCrossposted => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Message has already been crossposted".to_string(),
+                    code: 16,
+                }),
+            ),
+            Self::FollowerNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Channel follower not found".to_string(),
+                    code: 17,
+                }),
+            ),
+            Self::ThreadNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Thread not found".to_string(),
+                    code: 18,
+                }),
+            ),
+            Self::ThreadArchived => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Thread is archived".to_string(),
+                    code: 19,
+                }),
+            ),
+            Self::ThreadAlreadyExists => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "A thread already exists for this message".to_string(),
+                    code: 20,
+                }),
+            ),
+            Self::TagNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Tag not found".to_string(),
+                    code: 21,
+                }),
+            ),
+            Self::MaximumTagsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of tags reached".to_string(),
+                    code: 22,
+                }),
+            ),
+            Self::InvalidEmoji => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid emoji".to_string(),
+                    code: 23,
+                }),
+            ),
+            Self::MaximumReactionsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of reactions reached".to_string(),
+                    code: 24,
+                }),
+            ),
+            Self::InvalidMessageReference => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Referenced message must be in the same channel".to_string(),
+                    code: 25,
+                }),
+            ),
+            Self::MaximumPinsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of pins reached".to_string(),
+                    code: 26,
+                }),
+            ),
+            Self::InvalidAttachmentType => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Attachment type not allowed".to_string(),
+                    code: 27,
+                }),
+            ),
+            Self::AttachmentTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorMessage {
+                    message: "Attachment too large".to_string(),
+                    code: 28,
+                }),
+            ),
+            Self::MaximumAttachmentsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of attachments reached".to_string(),
+                    code: 29,
+                }),
+            ),
+            Self::InvalidSignature => (
+                StatusCode::FORBIDDEN,
+                Json(ErrorMessage {
+                    message: "Invalid or expired signature".to_string(),
+                    code: 30,
+                }),
+            ),
+            Self::AttachmentNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Attachment not found".to_string(),
+                    code: 31,
+                }),
+            ),
+            Self::InvalidImage => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid, unsupported or oversized image".to_string(),
+                    code: 32,
+                }),
+            ),
+            Self::AssetNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Asset not found".to_string(),
+                    code: 33,
+                }),
+            ),
+            Self::RateLimited => (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorMessage {
+                    message: "You are being rate limited".to_string(),
+                    code: 34,
+                }),
+            ),
+            Self::WebhookNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Webhook not found".to_string(),
+                    code: 35,
+                }),
+            ),
+            Self::SubscriptionNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Event subscription not found".to_string(),
+                    code: 36,
+                }),
+            ),
+            Self::MaximumSubscriptionsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of event subscriptions reached".to_string(),
+                    code: 37,
+                }),
+            ),
+            Self::ApplicationNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Application not found".to_string(),
+                    code: 38,
+                }),
+            ),
+            Self::CommandNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Command not found".to_string(),
+                    code: 39,
+                }),
+            ),
+            Self::InvalidCommand => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid command or command options".to_string(),
+                    code: 40,
+                }),
+            ),
+            Self::MaximumCommandsReached => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Maximum number of commands reached".to_string(),
+                    code: 41,
+                }),
+            ),
+            Self::InteractionNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Interaction not found".to_string(),
+                    code: 42,
+                }),
+            ),
+            Self::InteractionAlreadyResponded => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Interaction has already been responded to".to_string(),
+                    code: 43,
+                }),
+            ),
+            Self::ApplicationUnavailable => (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorMessage {
+                    message: "Application did not respond".to_string(),
+                    code: 44,
+                }),
+            ),
+            Self::InvalidComponents => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid message components".to_string(),
+                    code: 45,
+                }),
+            ),
+            Self::ComponentNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Component not found".to_string(),
+                    code: 46,
+                }),
+            ),
+            Self::InvalidEmbeds => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Invalid message embeds".to_string(),
+                    code: 47,
+                }),
+            ),
+            Self::PollNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Poll not found".to_string(),
+                    code: 48,
+                }),
+            ),
+            Self::PollAnswerNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorMessage {
+                    message: "Poll answer not found".to_string(),
+                    code: 49,
+                }),
+            ),
+            Self::PollClosed => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorMessage {
+                    message: "Poll has already ended".to_string(),
+                    code: 50,
+                }),
+            ),
         }
     }
 }
@@ -25,5 +25,26 @@ bitflags! {
         const VIEW_GUILD_INVITE_LIST = 1 << 5;
         const CREATE_INVITES = 1 << 6;
         const MANAGE_INVITES = 1 << 7;
+        const ADD_REACTIONS = 1 << 8;
+        const MENTION_EVERYONE = 1 << 9;
+        const MANAGE_WEBHOOKS = 1 << 10;
+    }
+}
+
+bitflags! {
+    pub struct MessageFlags: i32 {
+        const CROSSPOSTED = 1;
+        const IS_CROSSPOST = 1 << 1;
+        /// Posted by the system to announce a pin, referencing the pinned message.
+        const PIN_NOTICE = 1 << 2;
+        /// Only shown to the user who invoked the command it responds to, and never stored.
+        const EPHEMERAL = 1 << 3;
+    }
+}
+
+bitflags! {
+    pub struct AccountFlags: i32 {
+        /// The account belongs to an application rather than a person.
+        const BOT = 1;
     }
 }
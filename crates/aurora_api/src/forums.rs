@@ -0,0 +1,600 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use aurora_db::{
+    channel::{Channel, ChannelType},
+    forum::{ForumPost, GuildTag},
+    guild::Guild,
+    message::Message,
+    thread::{Thread, ThreadMember, ThreadMetadata},
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, patch, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_valid::Validate;
+use sqlx::PgPool;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    mentions::resolve_mentions,
+    messages::advance_last_message_id,
+    pubsub::{publish_guild, publish_user, Event},
+    state::OVTState,
+    threads::{get_thread, verify_thread_manager, verify_thread_permissions},
+    token::get_user,
+};
+
+const MAX_GUILD_TAGS: i64 = 20;
+
+/// Fetches a thread and makes sure it's a post in a forum channel.
+async fn get_forum_post(
+    db: &PgPool,
+    thread_id: &str,
+    guild_id: &str,
+) -> Result<Thread, (StatusCode, Json<ErrorMessage>)> {
+    let thread = get_thread(db, thread_id, guild_id).await?;
+    let parent = get_channel(
+        db,
+        thread.channel.parent_id.as_deref().unwrap_or_default(),
+        guild_id,
+    )
+    .await?;
+
+    if parent.r#type != ChannelType::Forum as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    Ok(thread)
+}
+
+/// Makes sure every tag exists in the guild, returning them deduplicated.
+async fn verify_tags(
+    db: &PgPool,
+    tag_ids: &[String],
+    guild_id: &str,
+) -> Result<Vec<String>, (StatusCode, Json<ErrorMessage>)> {
+    let tag_ids: Vec<String> = tag_ids
+        .iter()
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let found = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM guild_tags WHERE id = ANY($1) AND guild_id = $2;",
+        &tag_ids,
+        guild_id
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .unwrap_or_default();
+
+    if found != tag_ids.len() as i64 {
+        return Err(OVTError::TagNotFound.to_resp());
+    }
+
+    Ok(tag_ids)
+}
+
+// tags
+
+pub async fn get_guild_tags(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<GuildTag>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    let tags = GuildTag::from_guild(&state.pg, &guild.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(tags))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateGuildTag {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 20)]
+    name: String,
+}
+
+pub async fn create_guild_tag(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateGuildTag>,
+) -> Result<Json<GuildTag>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // lock the guild so concurrent creates can't go over the cap
+    sqlx::query!("SELECT id FROM guilds WHERE id = $1 FOR UPDATE;", &guild.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let tag_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM guild_tags WHERE guild_id = $1;",
+        &guild.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .unwrap_or_default();
+
+    if tag_count >= MAX_GUILD_TAGS {
+        return Err(OVTError::MaximumTagsReached.to_resp());
+    }
+
+    let tag = sqlx::query_as!(
+        GuildTag,
+        "INSERT INTO guild_tags (id, guild_id, name) VALUES ($1, $2, $3) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &guild.id,
+        model.name.trim()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::GuildTagCreate(tag.clone())).await?;
+
+    Ok(Json(tag))
+}
+
+pub async fn modify_guild_tag(
+    headers: HeaderMap,
+    Path((guild_id, tag_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateGuildTag>,
+) -> Result<Json<GuildTag>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
+
+    let tag = sqlx::query_as!(
+        GuildTag,
+        "UPDATE guild_tags SET name = $3 WHERE id = $1 AND guild_id = $2 RETURNING *;",
+        tag_id,
+        &guild.id,
+        model.name.trim()
+    )
+    .fetch_optional(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::TagNotFound.to_resp())?;
+
+    publish_guild(&guild.id, Event::GuildTagUpdate(tag.clone())).await?;
+
+    Ok(Json(tag))
+}
+
+pub async fn delete_guild_tag(
+    headers: HeaderMap,
+    Path((guild_id, tag_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_CHANNELS).await?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM guild_tags WHERE id = $1 AND guild_id = $2;",
+        &tag_id,
+        &guild.id
+    )
+    .execute(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(OVTError::TagNotFound.to_resp());
+    }
+
+    publish_guild(&guild.id, Event::GuildTagDelete(tag_id)).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+// posts
+
+fn default_auto_archive_duration() -> i32 {
+    4320
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateForumPost {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 100)]
+    title: String,
+    #[validate(min_length = 1)]
+    #[validate(max_length = 2048)]
+    content: String,
+    #[serde(default)]
+    #[validate(max_items = 5)]
+    applied_tags: Vec<String>,
+    #[serde(default = "default_auto_archive_duration")]
+    #[validate(enumerate = [60, 1440, 4320, 10080])]
+    auto_archive_duration: i32,
+}
+
+/// Starts a new post in a forum channel, the post's content becomes the first message in its thread.
+pub async fn create_forum_post(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateForumPost>,
+) -> Result<Json<ForumPost>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let forum = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
+
+    if forum.r#type != ChannelType::Forum as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let applied_tags = verify_tags(&state.pg, &model.applied_tags, &guild.id).await?;
+    let mentions = resolve_mentions(&state.pg, &actor, &guild, &model.content).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let channel = sqlx::query_as!(
+        Channel,
+        "INSERT INTO channels (id, name, guild_id, position, type, parent_id) VALUES ($1, $2, $3, 0, $4, $5) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        model.title.trim(),
+        &guild.id,
+        ChannelType::Thread as i32,
+        &forum.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let message = sqlx::query_as!(
+        Message,
+        "INSERT INTO messages (id, author_id, channel_id, content, mentions, mention_roles, mention_channels, mention_everyone)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &actor.id,
+        &channel.id,
+        model.content,
+        &mentions.users,
+        &mentions.roles,
+        &mentions.channels,
+        mentions.everyone
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    advance_last_message_id(&mut tx, &channel.id, &message.id).await?;
+    let thread_metadata = sqlx::query_as!(
+        ThreadMetadata,
+        "INSERT INTO thread_metadata (id, message_id, owner_id, auto_archive_duration) VALUES ($1, $2, $3, $4) RETURNING *;",
+        &channel.id,
+        &message.id,
+        &actor.id,
+        model.auto_archive_duration
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let member = sqlx::query_as!(
+        ThreadMember,
+        "INSERT INTO thread_members (thread_id, user_id) VALUES ($1, $2) RETURNING *;",
+        &channel.id,
+        &actor.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "INSERT INTO thread_tags (thread_id, tag_id) SELECT $1, UNNEST($2::TEXT[]);",
+        &channel.id,
+        &applied_tags
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let mut applied_tags = applied_tags;
+    applied_tags.sort();
+    let post = ForumPost {
+        thread: Thread {
+            channel,
+            thread_metadata,
+        },
+        applied_tags,
+    };
+
+    publish_guild(&guild.id, Event::ForumPostCreate(post.clone())).await?;
+    publish_guild(&guild.id, Event::MessageCreate(message.clone())).await?;
+    publish_guild(&guild.id, Event::ThreadMemberAdd(member)).await?;
+
+    for user_id in mentions
+        .users
+        .iter()
+        .filter(|user_id| **user_id != actor.id)
+    {
+        publish_user(user_id, Event::MessageMention(message.clone())).await?;
+    }
+
+    Ok(Json(post))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForumSortOrder {
+    #[default]
+    LatestActivity,
+    Creation,
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct GetForumPostsFilter {
+    sort: ForumSortOrder,
+    archived: bool,
+    tag_id: Option<String>,
+    #[validate(minimum = 1)]
+    #[validate(maximum = 100)]
+    limit: i64,
+}
+
+impl Default for GetForumPostsFilter {
+    fn default() -> Self {
+        Self {
+            sort: ForumSortOrder::default(),
+            archived: false,
+            tag_id: None,
+            limit: 25,
+        }
+    }
+}
+
+pub async fn get_forum_posts(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    Query(filters): Query<GetForumPostsFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ForumPost>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let forum = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    if forum.r#type != ChannelType::Forum as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let posts = ForumPost::from_forum(
+        &state.pg,
+        &forum.id,
+        filters.archived,
+        filters.sort == ForumSortOrder::LatestActivity,
+        filters.tag_id.as_deref(),
+        filters.limit,
+    )
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(posts))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ModifyForumPostTags {
+    #[validate(max_items = 5)]
+    applied_tags: Vec<String>,
+}
+
+pub async fn modify_forum_post_tags(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<ModifyForumPostTags>,
+) -> Result<Json<ForumPost>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_forum_post(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_manager(&state.pg, &actor, &guild, &thread).await?;
+
+    let applied_tags = verify_tags(&state.pg, &model.applied_tags, &guild.id).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    sqlx::query!(
+        "DELETE FROM thread_tags WHERE thread_id = $1;",
+        &thread.channel.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "INSERT INTO thread_tags (thread_id, tag_id) SELECT $1, UNNEST($2::TEXT[]);",
+        &thread.channel.id,
+        &applied_tags
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let post = ForumPost::from_id(&state.pg, thread.channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ForumPostUpdate(post.clone())).await?;
+
+    Ok(Json(post))
+}
+
+async fn set_forum_post_solved(
+    headers: HeaderMap,
+    guild_id: String,
+    thread_id: String,
+    state: OVTState,
+    solved: bool,
+) -> Result<Json<ForumPost>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_forum_post(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        &thread,
+        GuildPermissions::MANAGE_MESSAGES,
+    )
+    .await?;
+
+    sqlx::query!(
+        "UPDATE thread_metadata SET solved = $2 WHERE id = $1;",
+        &thread.channel.id,
+        solved
+    )
+    .execute(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let post = ForumPost::from_id(&state.pg, thread.channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ForumPostUpdate(post.clone())).await?;
+
+    Ok(Json(post))
+}
+
+pub async fn solve_forum_post(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<ForumPost>, (StatusCode, Json<ErrorMessage>)> {
+    set_forum_post_solved(headers, guild_id, thread_id, state, true).await
+}
+
+pub async fn unsolve_forum_post(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<ForumPost>, (StatusCode, Json<ErrorMessage>)> {
+    set_forum_post_solved(headers, guild_id, thread_id, state, false).await
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/tags",
+            get(get_guild_tags).post(create_guild_tag),
+        )
+        .route(
+            "/guilds/:guild_id/tags/:tag_id",
+            patch(modify_guild_tag).delete(delete_guild_tag),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/posts",
+            get(get_forum_posts).post(create_forum_post),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/tags",
+            put(modify_forum_post_tags),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/solved",
+            put(solve_forum_post).delete(unsolve_forum_post),
+        )
+}
@@ -18,9 +18,9 @@ use aurora_db::{
     FromId,
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
-    routing::{delete, patch, post},
+    routing::{delete, get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
@@ -112,6 +112,79 @@ pub async fn create_guild(
     Ok(Json(guild))
 }
 
+pub async fn get_guild(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Guild>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    Ok(Json(guild))
+}
+
+pub async fn get_user_guilds(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Guild>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+
+    let guilds = Guild::from_member(&state.pg, &actor.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(guilds))
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct GetGuildMembersFilter {
+    #[validate(minimum = 1)]
+    #[validate(maximum = 1000)]
+    limit: i64,
+    after: Option<String>,
+}
+
+impl Default for GetGuildMembersFilter {
+    fn default() -> Self {
+        Self {
+            limit: 100,
+            after: None,
+        }
+    }
+}
+
+pub async fn get_guild_members(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    Query(filters): Query<GetGuildMembersFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<GuildMember>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+
+    let members = GuildMember::from_guild(
+        &state.pg,
+        &guild.id,
+        filters.after.as_deref(),
+        filters.limit,
+    )
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(members))
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct ModifyGuild {
     #[serde(default)]
@@ -334,8 +407,9 @@ pub fn router() -> Router<OVTState> {
         .route("/guilds", post(create_guild))
         .route(
             "/guilds/:guild_id",
-            patch(modify_guild).delete(delete_guild),
+            get(get_guild).patch(modify_guild).delete(delete_guild),
         )
+        .route("/guilds/:guild_id/members", get(get_guild_members))
         .route(
             "/guilds/:guild_id/invites",
             post(create_invite).get(get_guild_invites),
@@ -345,5 +419,6 @@ pub fn router() -> Router<OVTState> {
             delete(delete_invite),
         )
         .route("/invites/:invite_id", post(use_invite))
+        .route("/users/@me/guilds", get(get_user_guilds))
         .route("/users/@me/guilds/:guild_id", delete(leave_guild))
 }
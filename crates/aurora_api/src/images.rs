@@ -0,0 +1,130 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::Cursor;
+
+use image::{imageops::FilterType, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+
+/// Largest width or height accepted before decoding.
+pub const MAX_IMAGE_DIMENSION: u32 = 8192;
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// Not an image, or in a format we don't take.
+    Unsupported,
+    /// Over the dimension or memory limits.
+    TooLarge,
+    /// Looked like an image but failed to decode or encode.
+    Invalid,
+}
+
+#[derive(Clone, Copy)]
+pub enum ImageKind {
+    Avatar,
+    Banner,
+    GuildIcon,
+}
+
+impl ImageKind {
+    /// Sizes every upload is resized to, largest first.
+    pub fn sizes(&self) -> &'static [(u32, u32)] {
+        match self {
+            Self::Avatar | Self::GuildIcon => &[(512, 512), (256, 256), (128, 128), (64, 64)],
+            Self::Banner => &[(1500, 500), (600, 200)],
+        }
+    }
+
+    /// The blob key prefix, also used in asset urls.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Avatar => "avatars",
+            Self::Banner => "banners",
+            Self::GuildIcon => "icons",
+        }
+    }
+
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "avatars" => Some(Self::Avatar),
+            "banners" => Some(Self::Banner),
+            "icons" => Some(Self::GuildIcon),
+            _ => None,
+        }
+    }
+}
+
+/// An image re-encoded as PNG in every size of its kind.
+pub struct ProcessedImage {
+    /// Hex SHA-256 of the largest variant, which addresses the whole set.
+    pub hash: String,
+    /// Widths paired with encoded PNGs, largest first.
+    pub variants: Vec<(u32, Vec<u8>)>,
+}
+
+fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| ImageError::Unsupported)?;
+
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP)
+    ) {
+        return Err(ImageError::Unsupported);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(256 * 1024 * 1024);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(to_image_error)?;
+    // EXIF is dropped on re-encoding, so its orientation has to be applied first
+    let orientation = decoder.orientation().map_err(to_image_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(to_image_error)?;
+    image.apply_orientation(orientation);
+
+    Ok(image)
+}
+
+fn to_image_error(err: image::ImageError) -> ImageError {
+    match err {
+        image::ImageError::Limits(_) => ImageError::TooLarge,
+        image::ImageError::Unsupported(_) => ImageError::Unsupported,
+        _ => ImageError::Invalid,
+    }
+}
+
+/// Decodes an upload, crops it to fill each size of `kind` and encodes the results as PNG,
+/// which leaves every bit of metadata from the original behind. Animated images keep their
+/// first frame.
+pub fn process_image(data: &[u8], kind: ImageKind) -> Result<ProcessedImage, ImageError> {
+    let image = decode(data)?;
+
+    let mut variants = Vec::with_capacity(kind.sizes().len());
+    for &(width, height) in kind.sizes() {
+        let mut encoded = Vec::new();
+        image
+            .resize_to_fill(width, height, FilterType::Lanczos3)
+            .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+            .map_err(|_| ImageError::Invalid)?;
+        variants.push((width, encoded));
+    }
+
+    let hash = hex::encode(Sha256::digest(&variants[0].1));
+    Ok(ProcessedImage { hash, variants })
+}
@@ -0,0 +1,632 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use aurora_db::{
+    application::{Application, ApplicationCommand, CommandOptionType, CommandValue, Interaction},
+    component::Component,
+    embed::Embed,
+    guild::Guild,
+    guild_member::GuildMember,
+    message::Message,
+    FromId,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{patch, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use sqlx::{Postgres, Transaction};
+
+use crate::{
+    channels::get_channel,
+    commands::{resolve_options, OptionValue},
+    components::{stored_components, verify_component_use, verify_components, ComponentError},
+    dispatch::sign_delivery,
+    embeds::{stored_embeds, verify_embeds},
+    error::{ErrorMessage, OVTError},
+    flags::{GuildPermissions, MessageFlags},
+    guilds::verify_permissions,
+    messages::{advance_last_message_id, get_message},
+    net::{parse_web_url, pinned_client},
+    pubsub::{publish_guild, publish_user, Event},
+    state::OVTState,
+    token::get_user,
+    webhooks::hash_token,
+};
+
+/// How long an application's interactions url has to answer an interaction.
+pub const INTERACTION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Far more than the largest valid response, anything longer isn't read.
+pub const MAX_INTERACTION_RESPONSE_SIZE: usize = 256 * 1024;
+
+#[derive(Deserialize)]
+pub struct CreateInteraction {
+    guild_id: String,
+    channel_id: String,
+    command_id: String,
+    #[serde(default)]
+    options: Vec<OptionValue>,
+}
+
+/// Using a button or select menu on a message sent by a bot.
+#[derive(Deserialize)]
+pub struct CreateComponentInteraction {
+    guild_id: String,
+    channel_id: String,
+    message_id: String,
+    custom_id: String,
+    /// The options picked in a select menu.
+    #[serde(default)]
+    values: Vec<String>,
+}
+
+/// What applications receive for each interaction, the token is needed to respond to it.
+#[derive(Serialize, Clone)]
+pub struct InteractionPayload {
+    #[serde(flatten)]
+    interaction: Interaction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command_name: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    options: Vec<OptionValue>,
+    /// The message a component was used on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Message>,
+    token: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct InteractionMessage {
+    /// Can only be left out when sending embeds.
+    #[serde(default)]
+    #[validate(max_length = 2048)]
+    content: String,
+    /// Ephemeral messages are only shown to the user who invoked the command, and never stored.
+    #[serde(default)]
+    ephemeral: bool,
+    #[serde(default)]
+    components: Vec<Component>,
+    #[serde(default)]
+    embeds: Vec<Embed>,
+}
+
+/// What an interactions url replies with, a deferred interaction is responded to later.
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum InteractionResponse {
+    Message(InteractionMessage),
+    /// Replaces the message a component was used on, only for component interactions.
+    UpdateMessage(InteractionMessage),
+    Deferred,
+}
+
+#[derive(Serialize)]
+pub struct CreatedInteraction {
+    interaction: Interaction,
+    message: Option<Message>,
+}
+
+/// Checks that user and channel values point at something in the guild.
+async fn verify_references(
+    state: &OVTState,
+    command: &ApplicationCommand,
+    guild: &Guild,
+    values: &[OptionValue],
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    for value in values {
+        let Some(option) = command
+            .options
+            .iter()
+            .find(|option| option.name == value.name)
+        else {
+            continue;
+        };
+        let CommandValue::String(id) = &value.value else {
+            continue;
+        };
+
+        match option.r#type {
+            CommandOptionType::User => {
+                GuildMember::from_id(&state.pg, (id, &guild.id))
+                    .await
+                    .map_err(|_| OVTError::InvalidBody.to_resp())?;
+            }
+            CommandOptionType::Channel => {
+                get_channel(&state.pg, id, &guild.id)
+                    .await
+                    .map_err(|_| OVTError::InvalidBody.to_resp())?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends an interaction to its application's interactions url, returning how it answered.
+async fn send_interaction(
+    url: &str,
+    secret: &str,
+    payload: &InteractionPayload,
+) -> Result<InteractionResponse, (StatusCode, Json<ErrorMessage>)> {
+    tokio::time::timeout(
+        INTERACTION_TIMEOUT,
+        request_interaction(url, secret, payload),
+    )
+    .await
+    .map_err(|_| OVTError::ApplicationUnavailable.to_resp())?
+}
+
+async fn request_interaction(
+    url: &str,
+    secret: &str,
+    payload: &InteractionPayload,
+) -> Result<InteractionResponse, (StatusCode, Json<ErrorMessage>)> {
+    let body =
+        serde_json::to_string(payload).map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let timestamp = chrono::Utc::now().timestamp();
+
+    // where the url resolves is checked again on every interaction, it may have changed since
+    let url = parse_web_url(url).ok_or(OVTError::ApplicationUnavailable.to_resp())?;
+    let client = pinned_client(&url, false)
+        .await
+        .ok_or(OVTError::ApplicationUnavailable.to_resp())?
+        .build()
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let mut response = client
+        .post(url)
+        .header("content-type", "application/json")
+        .header("x-aurora-event", "InteractionCreate")
+        .header("x-aurora-delivery", &payload.interaction.id)
+        .header("x-aurora-timestamp", timestamp.to_string())
+        .header(
+            "x-aurora-signature",
+            sign_delivery(secret, timestamp, &body),
+        )
+        .body(body)
+        .send()
+        .await
+        .map_err(|_| OVTError::ApplicationUnavailable.to_resp())?;
+    if !response.status().is_success() {
+        return Err(OVTError::ApplicationUnavailable.to_resp());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|_| OVTError::ApplicationUnavailable.to_resp())?
+    {
+        if body.len() + chunk.len() > MAX_INTERACTION_RESPONSE_SIZE {
+            return Err(OVTError::ApplicationUnavailable.to_resp());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    serde_json::from_slice(&body).map_err(|_| OVTError::ApplicationUnavailable.to_resp())
+}
+
+fn verify_response(model: &InteractionMessage) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    if model.content.is_empty() && model.embeds.is_empty() {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+    verify_embeds(&model.embeds).map_err(|_| OVTError::InvalidEmbeds.to_resp())?;
+    // nobody else could ever use components on a message which isn't stored
+    if !model.components.is_empty()
+        && (model.ephemeral || verify_components(&model.components).is_err())
+    {
+        return Err(OVTError::InvalidComponents.to_resp());
+    }
+
+    Ok(())
+}
+
+/// Marks an interaction as responded to, which can only happen once and within 15 minutes of it
+/// being created.
+async fn claim_response(
+    tx: &mut Transaction<'_, Postgres>,
+    interaction: &Interaction,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    sqlx::query!(
+        "UPDATE interactions SET responded = true WHERE id = $1 AND NOT responded AND created_at > NOW() - INTERVAL '15 minutes' RETURNING id;",
+        &interaction.id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or(OVTError::InteractionAlreadyResponded.to_resp())?;
+
+    Ok(())
+}
+
+/// Responds to an interaction with a new message.
+async fn respond(
+    state: &OVTState,
+    interaction: &Interaction,
+    application: &Application,
+    model: InteractionMessage,
+) -> Result<Message, (StatusCode, Json<ErrorMessage>)> {
+    verify_response(&model)?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    claim_response(&mut tx, interaction).await?;
+
+    if model.ephemeral {
+        tx.commit()
+            .await
+            .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+        let message = Message {
+            id: uuid7::uuid7().to_string(),
+            author_id: Some(application.bot_id.clone()),
+            channel_id: interaction.channel_id.clone(),
+            content: model.content,
+            flags: MessageFlags::EPHEMERAL.bits(),
+            source_guild_id: None,
+            source_channel_id: None,
+            source_message_id: None,
+            edited_at: None,
+            reference_id: None,
+            mentions: Vec::new(),
+            mention_roles: Vec::new(),
+            mention_channels: Vec::new(),
+            mention_everyone: false,
+            pinned_at: None,
+            webhook_id: None,
+            webhook_name: None,
+            webhook_avatar_url: None,
+            interaction_id: Some(interaction.id.clone()),
+            components: None,
+            embeds: stored_embeds(&model.embeds),
+        };
+        publish_user(&interaction.user_id, Event::MessageCreate(message.clone())).await?;
+
+        return Ok(message);
+    }
+
+    let message = sqlx::query_as!(
+        Message,
+        "INSERT INTO messages (id, author_id, channel_id, content, interaction_id, components, embeds) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &application.bot_id,
+        &interaction.channel_id,
+        model.content,
+        &interaction.id,
+        stored_components(&model.components),
+        stored_embeds(&model.embeds)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    advance_last_message_id(&mut tx, &interaction.channel_id, &message.id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&interaction.guild_id, Event::MessageCreate(message.clone())).await?;
+
+    Ok(message)
+}
+
+/// Responds to a component interaction by replacing the message the component was used on.
+async fn update_message(
+    state: &OVTState,
+    interaction: &Interaction,
+    application: &Application,
+    model: InteractionMessage,
+) -> Result<Message, (StatusCode, Json<ErrorMessage>)> {
+    verify_response(&model)?;
+    let Some(message_id) = &interaction.message_id else {
+        return Err(OVTError::InvalidBody.to_resp());
+    };
+    if model.ephemeral {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    claim_response(&mut tx, interaction).await?;
+
+    let message = sqlx::query_as!(
+        Message,
+        "SELECT * FROM messages WHERE id = $1 AND author_id = $2 FOR UPDATE;",
+        message_id,
+        &application.bot_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::MessageNotFound.to_resp())?;
+
+    if message.content != model.content {
+        sqlx::query!(
+            "INSERT INTO message_revisions (id, message_id, content) VALUES ($1, $2, $3);",
+            uuid7::uuid7().to_string(),
+            &message.id,
+            &message.content
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    }
+    let modified_message = sqlx::query_as!(
+        Message,
+        "UPDATE messages SET content = $2, components = $3, embeds = $4, edited_at = now() WHERE id = $1 RETURNING *;",
+        &message.id,
+        model.content,
+        stored_components(&model.components),
+        stored_embeds(&model.embeds)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(
+        &interaction.guild_id,
+        Event::MessageModified(modified_message.clone()),
+    )
+    .await?;
+
+    Ok(modified_message)
+}
+
+/// Sends an interaction to its application over its interactions url when it has one, and over
+/// the gateway otherwise, returning the message it was answered with right away.
+async fn route_interaction(
+    state: &OVTState,
+    application: &Application,
+    interaction: &Interaction,
+    payload: InteractionPayload,
+) -> Result<Option<Message>, (StatusCode, Json<ErrorMessage>)> {
+    let Some(url) = &application.interactions_url else {
+        publish_user(
+            &application.bot_id,
+            Event::InteractionCreate(Box::new(payload)),
+        )
+        .await?;
+        return Ok(None);
+    };
+
+    match send_interaction(url, &application.interactions_secret, &payload).await? {
+        InteractionResponse::Message(reply) => {
+            Ok(Some(respond(state, interaction, application, reply).await?))
+        }
+        InteractionResponse::UpdateMessage(reply) if interaction.message_id.is_some() => Ok(Some(
+            update_message(state, interaction, application, reply).await?,
+        )),
+        InteractionResponse::UpdateMessage(_) => Err(OVTError::ApplicationUnavailable.to_resp()),
+        InteractionResponse::Deferred => Ok(None),
+    }
+}
+
+fn interaction_token() -> String {
+    let mut token = [0u8; 32];
+    OsRng.fill_bytes(&mut token);
+    hex::encode(token)
+}
+
+/// Invokes a command, routing it to the application over its interactions url when it has one
+/// and over the gateway otherwise.
+pub async fn create_interaction(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateInteraction>,
+) -> Result<Json<CreatedInteraction>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, model.guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &model.channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
+
+    let command = ApplicationCommand::from_id(&state.pg, model.command_id)
+        .await
+        .map_err(|_| OVTError::CommandNotFound.to_resp())?;
+    if command.guild_id.as_ref().is_some_and(|id| *id != guild.id) {
+        return Err(OVTError::CommandNotFound.to_resp());
+    }
+    let application = Application::from_id(&state.pg, command.application_id.clone())
+        .await
+        .map_err(|_| OVTError::CommandNotFound.to_resp())?;
+    GuildMember::from_id(&state.pg, (&application.bot_id, &guild.id))
+        .await
+        .map_err(|_| OVTError::CommandNotFound.to_resp())?;
+
+    let options = resolve_options(&command.options, model.options)
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    verify_references(&state, &command, &guild, &options).await?;
+
+    let token = interaction_token();
+
+    let interaction = sqlx::query_as!(
+        Interaction,
+        "INSERT INTO interactions (id, application_id, command_id, guild_id, channel_id, user_id, token_hash) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &application.id,
+        &command.id,
+        &guild.id,
+        &channel.id,
+        &actor.id,
+        hash_token(&token)
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let payload = InteractionPayload {
+        interaction: interaction.clone(),
+        command_name: Some(command.name),
+        options,
+        message: None,
+        token,
+    };
+    let message = route_interaction(&state, &application, &interaction, payload).await?;
+
+    Ok(Json(CreatedInteraction {
+        interaction,
+        message,
+    }))
+}
+
+/// Uses a component on a message, routing it to the application whose bot sent the message.
+pub async fn create_component_interaction(
+    headers: HeaderMap,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateComponentInteraction>,
+) -> Result<Json<CreatedInteraction>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, model.guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &model.channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
+
+    let message = get_message(&state.pg, model.message_id, &channel.id).await?;
+    let components: Vec<Component> = match &message.components {
+        Some(components) => serde_json::from_value(components.clone())
+            .map_err(|_| OVTError::InternalServerError.to_resp())?,
+        None => Vec::new(),
+    };
+    verify_component_use(&components, &model.custom_id, &model.values).map_err(
+        |err| match err {
+            ComponentError::UnknownComponent(_) => OVTError::ComponentNotFound.to_resp(),
+            _ => OVTError::InvalidBody.to_resp(),
+        },
+    )?;
+
+    // only bots send components, so this only fails once the bot is gone
+    let application = Application::from_bot(&state.pg, message.author_id.as_deref().unwrap_or(""))
+        .await
+        .map_err(|_| OVTError::ComponentNotFound.to_resp())?;
+    GuildMember::from_id(&state.pg, (&application.bot_id, &guild.id))
+        .await
+        .map_err(|_| OVTError::ComponentNotFound.to_resp())?;
+
+    let token = interaction_token();
+    let interaction = sqlx::query_as!(
+        Interaction,
+        "INSERT INTO interactions (id, application_id, message_id, custom_id, component_values, guild_id, channel_id, user_id, token_hash) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &application.id,
+        &message.id,
+        &model.custom_id,
+        &model.values,
+        &guild.id,
+        &channel.id,
+        &actor.id,
+        hash_token(&token)
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let payload = InteractionPayload {
+        interaction: interaction.clone(),
+        command_name: None,
+        options: Vec::new(),
+        message: Some(message),
+        token,
+    };
+    let message = route_interaction(&state, &application, &interaction, payload).await?;
+
+    Ok(Json(CreatedInteraction {
+        interaction,
+        message,
+    }))
+}
+
+/// Checks the token an application responds to an interaction with.
+async fn get_interaction(
+    state: &OVTState,
+    interaction_id: String,
+    token: &str,
+) -> Result<(Interaction, Application), (StatusCode, Json<ErrorMessage>)> {
+    let interaction = Interaction::from_id(&state.pg, interaction_id)
+        .await
+        .map_err(|_| OVTError::InteractionNotFound.to_resp())?;
+    if interaction.token_hash != hash_token(token) {
+        return Err(OVTError::InteractionNotFound.to_resp());
+    }
+    let application = Application::from_id(&state.pg, interaction.application_id.clone())
+        .await
+        .map_err(|_| OVTError::InteractionNotFound.to_resp())?;
+
+    Ok((interaction, application))
+}
+
+/// Lets an application respond to an interaction it was sent, with the token it was sent with.
+pub async fn create_interaction_response(
+    Path((interaction_id, token)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<InteractionMessage>,
+) -> Result<Json<Message>, (StatusCode, Json<ErrorMessage>)> {
+    let (interaction, application) = get_interaction(&state, interaction_id, &token).await?;
+
+    Ok(Json(
+        respond(&state, &interaction, &application, model).await?,
+    ))
+}
+
+/// Lets an application respond to a component interaction by updating the message it was used on.
+pub async fn modify_interaction_message(
+    Path((interaction_id, token)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<InteractionMessage>,
+) -> Result<Json<Message>, (StatusCode, Json<ErrorMessage>)> {
+    let (interaction, application) = get_interaction(&state, interaction_id, &token).await?;
+
+    Ok(Json(
+        update_message(&state, &interaction, &application, model).await?,
+    ))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route("/interactions", post(create_interaction))
+        .route(
+            "/interactions/components",
+            post(create_component_interaction),
+        )
+        .route(
+            "/interactions/:interaction_id/:token/callback",
+            post(create_interaction_response),
+        )
+        .route(
+            "/interactions/:interaction_id/:token/message",
+            patch(modify_interaction_message),
+        )
+}
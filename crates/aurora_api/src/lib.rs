@@ -14,4 +14,14 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod token;
+pub mod commands;
+pub mod components;
+pub mod embeds;
+pub mod dispatch;
+pub mod edits;
 pub mod error;
+pub mod images;
+pub mod net;
+pub mod ratelimit;
+pub mod storage;
+pub mod unfurl;
@@ -15,23 +15,52 @@
 
 #![feature(duration_constructors)]
 
-use std::{env, time::Duration};
+use std::{env, sync::Arc, time::Duration};
 
 use axum::{http::Method, Router};
+use dispatch::DeliveryConfig;
+use ratelimit::RateLimiter;
 use sqlx::postgres::PgPoolOptions;
 use state::OVTState;
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::mpsc};
 use tower_http::cors::{Any, CorsLayer};
+use unfurl::{UnfurlConfig, Unfurler};
 
+mod applications;
+mod assets;
+mod attachments;
+mod bulk_delete;
 mod channels;
+mod commands;
+mod components;
+mod dispatch;
+mod edits;
+mod embeds;
 mod error;
 mod flags;
+mod forums;
 mod guilds;
+mod images;
+mod interactions;
+mod mentions;
 mod messages;
+mod net;
+mod pins;
+mod polls;
 mod pubsub;
+mod ratelimit;
+mod reactions;
+mod read_states;
+mod search;
 mod state;
+mod storage;
+mod subscriptions;
+mod threads;
 mod token;
+mod typing;
+mod unfurl;
 mod users;
+mod webhooks;
 
 #[tokio::main]
 async fn main() {
@@ -47,13 +76,49 @@ async fn main() {
         .await
         .expect("can't connect to database");
 
+    tokio::spawn(threads::archive_inactive_threads(pool.clone()));
+    tokio::spawn(polls::close_expired_polls(pool.clone()));
+
+    let (dispatcher, events) = mpsc::unbounded_channel();
+    pubsub::set_dispatcher(dispatcher);
+    tokio::spawn(dispatch::run_dispatcher(
+        pool.clone(),
+        DeliveryConfig::default(),
+        events,
+    ));
+
+    let (unfurler, jobs) = mpsc::unbounded_channel();
+    messages::set_unfurler(unfurler);
+    tokio::spawn(unfurl::run_unfurler(
+        Unfurler::new(pool.clone(), UnfurlConfig::default()),
+        jobs,
+        |job, message| async move {
+            let _ =
+                pubsub::publish_guild(&job.guild_id, pubsub::Event::MessageModified(message)).await;
+        },
+    ));
+
+    let key = env::var("JWT_SECRET_KEY").unwrap();
     let state = OVTState {
         pg: pool,
-        key: env::var("JWT_SECRET_KEY").unwrap(),
+        download_key: storage::download_key(&key),
+        key,
+        blobs: storage::blob_store_from_env(),
+        typing: Arc::new(RateLimiter::new(1, typing::TYPING_RATE_LIMIT)),
+        webhooks: Arc::new(RateLimiter::new(
+            webhooks::WEBHOOK_RATE_LIMIT,
+            webhooks::WEBHOOK_RATE_LIMIT_WINDOW,
+        )),
     };
 
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PATCH])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::PATCH,
+        ])
         .allow_headers(Any)
         .allow_origin(Any);
 
@@ -62,6 +127,21 @@ async fn main() {
         .merge(guilds::router())
         .merge(channels::router())
         .merge(messages::router())
+        .merge(threads::router())
+        .merge(forums::router())
+        .merge(reactions::router())
+        .merge(pins::router())
+        .merge(polls::router())
+        .merge(attachments::router())
+        .merge(assets::router())
+        .merge(search::router())
+        .merge(read_states::router())
+        .merge(typing::router())
+        .merge(bulk_delete::router())
+        .merge(webhooks::router())
+        .merge(subscriptions::router())
+        .merge(applications::router())
+        .merge(interactions::router())
         .layer(cors)
         .with_state(state);
 
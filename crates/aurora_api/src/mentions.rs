@@ -0,0 +1,110 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{actor::Actor, guild::Guild};
+use aurora_markdown::Node;
+use axum::{http::StatusCode, Json};
+use sqlx::PgPool;
+
+use crate::{
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+};
+
+/// Maximum number of mentions of each kind kept for a single message.
+pub const MAX_MENTIONS: usize = 100;
+
+#[derive(Default)]
+pub struct Mentions {
+    pub users: Vec<String>,
+    pub roles: Vec<String>,
+    pub channels: Vec<String>,
+    pub everyone: bool,
+}
+
+/// Picks `<@user>`, `<@&role>`, `<#channel>` and `@everyone` mentions out of message content,
+/// the same way clients are told to render it so nothing in code or link text pings anyone.
+pub fn parse_mentions(content: &str) -> Mentions {
+    let mut mentions = Mentions::default();
+
+    aurora_markdown::parse(content).walk(&mut |node| {
+        let (kind, id) = match node {
+            Node::UserMention { id } => (&mut mentions.users, id),
+            Node::RoleMention { id } => (&mut mentions.roles, id),
+            Node::ChannelMention { id } => (&mut mentions.channels, id),
+            Node::Everyone => {
+                mentions.everyone = true;
+                return;
+            }
+            _ => return,
+        };
+
+        if kind.len() < MAX_MENTIONS && !kind.iter().any(|mentioned| mentioned == id) {
+            kind.push(id.clone());
+        }
+    });
+
+    mentions
+}
+
+/// Parses mentions and drops the ones which don't point at anything in the guild.
+pub async fn resolve_mentions(
+    db: &PgPool,
+    actor: &Actor,
+    guild: &Guild,
+    content: &str,
+) -> Result<Mentions, (StatusCode, Json<ErrorMessage>)> {
+    let mut mentions = parse_mentions(content);
+
+    if !mentions.users.is_empty() {
+        mentions.users = sqlx::query!(
+            "SELECT user_id FROM guild_members WHERE guild_id = $1 AND user_id = ANY($2);",
+            &guild.id,
+            &mentions.users
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?
+        .into_iter()
+        .map(|row| row.user_id)
+        .collect();
+    }
+
+    if !mentions.channels.is_empty() {
+        mentions.channels = sqlx::query!(
+            "SELECT id FROM channels WHERE guild_id = $1 AND id = ANY($2);",
+            &guild.id,
+            &mentions.channels
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?
+        .into_iter()
+        .map(|row| row.id)
+        .collect();
+    }
+
+    // TODO: check role mentions against the guild once roles happen
+
+    if mentions.everyone {
+        mentions.everyone =
+            verify_permissions(db, actor, guild, GuildPermissions::MENTION_EVERYONE)
+                .await
+                .is_ok();
+    }
+
+    Ok(mentions)
+}
@@ -13,35 +13,78 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use aurora_db::{guild::Guild, message::Message, FromId};
+use std::sync::OnceLock;
+
+use aurora_db::{
+    attachment::Attachment,
+    channel::ChannelType,
+    channel_follower::ChannelFollower,
+    component::Component,
+    embed::Embed,
+    guild::Guild,
+    message::{FullMessage, Message, MessageCursor},
+    message_revision::MessageRevision,
+    FromId,
+};
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    routing::{patch, post},
+    async_trait,
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post},
     Json, Router,
 };
 use serde::Deserialize;
 use serde_valid::Validate;
+use sqlx::{PgConnection, PgPool};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
+    attachments::{
+        delete_blobs, sign_attachments, store_uploads, StoredUpload, Upload, MAX_ATTACHMENTS,
+        MAX_UPLOAD_REQUEST_SIZE,
+    },
     channels::get_channel,
+    components::{stored_components, verify_components},
+    edits::ModifyMessage,
+    embeds::{stored_embeds, verify_embeds},
     error::{ErrorMessage, OVTError},
-    flags::GuildPermissions,
+    flags::{AccountFlags, GuildPermissions, MessageFlags},
     guilds::verify_permissions,
-    pubsub::{publish_guild, Event},
+    mentions::{resolve_mentions, Mentions},
+    polls::{insert_poll, CreatePoll},
+    pubsub::{publish_guild, publish_user, Event},
     state::OVTState,
+    threads::bump_thread_activity,
     token::get_user,
+    unfurl::{extract_urls, UnfurlJob},
 };
 
+pub async fn get_message(
+    db: &PgPool,
+    message_id: String,
+    channel_id: &str,
+) -> Result<Message, (StatusCode, Json<ErrorMessage>)> {
+    let message = Message::from_id(db, message_id)
+        .await
+        .map_err(|_| OVTError::MessageNotFound.to_resp())?;
+
+    if message.channel_id != channel_id {
+        return Err(OVTError::MessageNotFound.to_resp());
+    }
+
+    Ok(message)
+}
+
 #[derive(Deserialize, Validate)]
+#[serde(default)]
 pub struct GetGuildChannelMessagesFilter {
     #[validate(minimum = 5)]
     #[validate(maximum = 256)]
     limit: i64,
-    #[serde(default)]
     before: Option<String>,
-    #[serde(default)]
     after: Option<String>,
+    around: Option<String>,
 }
 
 impl Default for GetGuildChannelMessagesFilter {
@@ -50,17 +93,35 @@ impl Default for GetGuildChannelMessagesFilter {
             limit: 30,
             before: None,
             after: None,
+            around: None,
+        }
+    }
+}
+
+impl GetGuildChannelMessagesFilter {
+    /// At most one of `before`, `after` and `around` may be given.
+    fn cursor(self) -> Result<MessageCursor, (StatusCode, Json<ErrorMessage>)> {
+        match (self.before, self.after, self.around) {
+            (None, None, None) => Ok(MessageCursor::Latest),
+            (Some(before), None, None) => Ok(MessageCursor::Before(before)),
+            (None, Some(after), None) => Ok(MessageCursor::After(after)),
+            (None, None, Some(around)) => Ok(MessageCursor::Around(around)),
+            _ => Err(OVTError::InvalidQuery.to_resp()),
         }
     }
 }
 
 pub async fn get_guild_channel_messages(
     headers: HeaderMap,
-    maybe_filters: Option<Query<GetGuildChannelMessagesFilter>>,
+    Query(filters): Query<GetGuildChannelMessagesFilter>,
     Path((guild_id, channel_id)): Path<(String, String)>,
     State(state): State<OVTState>,
-) -> Result<Json<Vec<Message>>, (StatusCode, Json<ErrorMessage>)> {
-    let Query(filters) = maybe_filters.unwrap_or_default();
+) -> Result<Json<Vec<FullMessage>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+    let limit = filters.limit;
+    let cursor = filters.cursor()?;
 
     let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
     let guild = Guild::from_id(&state.pg, guild_id)
@@ -75,85 +136,427 @@ pub async fn get_guild_channel_messages(
     )
     .await?;
 
-    let messages = sqlx::query_as!(
-        Message,
-        "SELECT * FROM messages WHERE channel_id = $1 AND id > $2 AND id < $3 LIMIT $4;",
-        &channel.id,
-        filters.after,
-        filters.before,
-        filters.limit
-    )
-    .fetch_all(&state.pg)
-    .await
-    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let messages = Message::paginate(&state.pg, &channel.id, cursor, limit)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let messages = hydrate_messages(&state, messages).await?;
 
     Ok(Json(messages))
 }
 
+#[derive(Deserialize)]
+pub struct MessageReference {
+    message_id: String,
+    /// Whether the author of the referenced message should be notified.
+    #[serde(default)]
+    notify_author: bool,
+}
+
 #[derive(Deserialize, Validate)]
 pub struct CreateMessage {
-    #[validate(min_length = 1)]
+    /// Can only be left out when sending embeds, a poll or attachments.
+    #[serde(default)]
     #[validate(max_length = 2048)]
     content: String,
+    #[serde(default)]
+    reference: Option<MessageReference>,
+    /// Only bots can send components.
+    #[serde(default)]
+    components: Vec<Component>,
+    /// Only bots and webhooks can send embeds.
+    #[serde(default)]
+    embeds: Vec<Embed>,
+    #[serde(default)]
+    #[validate]
+    poll: Option<CreatePoll>,
+}
+
+/// A message to create, sent either as JSON or as multipart form data with a
+/// `payload_json` field next to its files.
+pub struct CreateMessageBody {
+    model: CreateMessage,
+    uploads: Vec<Upload>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for CreateMessageBody {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+
+        if !is_multipart {
+            let Json(model) = Json::<CreateMessage>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self {
+                model,
+                uploads: Vec::new(),
+            });
+        }
+
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut model = None;
+        let mut uploads = Vec::new();
+
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|_| OVTError::InvalidBody.to_resp().into_response())?
+        {
+            if field.name() == Some("payload_json") {
+                let payload = field
+                    .text()
+                    .await
+                    .map_err(|_| OVTError::InvalidBody.to_resp().into_response())?;
+                model = Some(
+                    serde_json::from_str(&payload)
+                        .map_err(|_| OVTError::InvalidBody.to_resp().into_response())?,
+                );
+            } else if field.file_name().is_some() {
+                if uploads.len() == MAX_ATTACHMENTS {
+                    return Err(OVTError::MaximumAttachmentsReached
+                        .to_resp()
+                        .into_response());
+                }
+                uploads.push(
+                    Upload::from_field(field)
+                        .await
+                        .map_err(IntoResponse::into_response)?,
+                );
+            }
+        }
+
+        let model = model.ok_or_else(|| OVTError::InvalidBody.to_resp().into_response())?;
+        Ok(Self { model, uploads })
+    }
+}
+
+/// Embeds referenced messages and signed attachment urls into messages.
+pub async fn hydrate_messages(
+    state: &OVTState,
+    messages: Vec<Message>,
+) -> Result<Vec<FullMessage>, (StatusCode, Json<ErrorMessage>)> {
+    let mut messages = Message::hydrate(&state.pg, messages)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sign_attachments(&state.download_key, &mut messages);
+
+    Ok(messages)
+}
+
+async fn hydrate_message(
+    state: &OVTState,
+    message: Message,
+) -> Result<FullMessage, (StatusCode, Json<ErrorMessage>)> {
+    hydrate_messages(state, vec![message])
+        .await?
+        .pop()
+        .ok_or_else(|| OVTError::InternalServerError.to_resp())
+}
+
+/// Points a channel at a new message, unless a newer one has already been committed.
+pub async fn advance_last_message_id(
+    tx: &mut PgConnection,
+    channel_id: &str,
+    message_id: &str,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    sqlx::query!(
+        "UPDATE channels SET last_message_id = $2 WHERE id = $1 AND (last_message_id IS NULL OR last_message_id COLLATE \"C\" < $2);",
+        channel_id,
+        message_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(())
+}
+
+/// Inserts a message together with its already stored attachments.
+async fn insert_message(
+    state: &OVTState,
+    author_id: &str,
+    channel_id: &str,
+    model: &CreateMessage,
+    referenced_message: Option<&Message>,
+    mentions: &Mentions,
+    uploads: &[StoredUpload],
+) -> Result<Message, (StatusCode, Json<ErrorMessage>)> {
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let message = sqlx::query_as!(
+        Message,
+        "INSERT INTO messages (id, author_id, channel_id, content, reference_id, mentions, mention_roles, mention_channels, mention_everyone, components, embeds)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        author_id,
+        channel_id,
+        model.content,
+        referenced_message.map(|referenced| &referenced.id),
+        &mentions.users,
+        &mentions.roles,
+        &mentions.channels,
+        mentions.everyone,
+        stored_components(&model.components),
+        stored_embeds(&model.embeds)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    advance_last_message_id(&mut tx, channel_id, &message.id).await?;
+
+    if let Some(poll) = &model.poll {
+        insert_poll(&mut tx, &message.id, channel_id, poll).await?;
+    }
+
+    for upload in uploads {
+        sqlx::query!(
+            "INSERT INTO attachments (id, message_id, filename, content_type, size, blob_key) VALUES ($1, $2, $3, $4, $5, $6);",
+            &upload.id,
+            &message.id,
+            &upload.filename,
+            &upload.content_type,
+            upload.size,
+            &upload.blob_key
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(message)
+}
+
+static UNFURLER: OnceLock<UnboundedSender<UnfurlJob>> = OnceLock::new();
+
+/// Hands new messages with links to the unfurler from now on.
+pub fn set_unfurler(unfurler: UnboundedSender<UnfurlJob>) {
+    let _ = UNFURLER.set(unfurler);
+}
+
+/// Queues the links in a published message to be previewed.
+pub fn queue_unfurl(guild_id: &str, message: &Message) {
+    if let Some(unfurler) = UNFURLER.get() {
+        if !extract_urls(&message.content).is_empty() {
+            let _ = unfurler.send(UnfurlJob {
+                guild_id: guild_id.to_string(),
+                message_id: message.id.clone(),
+            });
+        }
+    }
 }
 
 pub async fn create_guild_channel_message(
     headers: HeaderMap,
     Path((guild_id, channel_id)): Path<(String, String)>,
     State(state): State<OVTState>,
-    Json(model): Json<CreateMessage>,
-) -> Result<Json<Message>, (StatusCode, Json<ErrorMessage>)> {
-    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    CreateMessageBody { model, uploads }: CreateMessageBody,
+) -> Result<Json<FullMessage>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    if model.content.is_empty()
+        && model.embeds.is_empty()
+        && model.poll.is_none()
+        && uploads.is_empty()
+    {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+
+    let (actor, account) = get_user(&headers, &state.key, &state.pg).await?;
     let guild = Guild::from_id(&state.pg, guild_id)
         .await
         .map_err(|_| OVTError::GuildNotFound.to_resp())?;
     let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
     verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
 
-    let message = sqlx::query_as!(
-        Message,
-        "INSERT INTO messages (id, author_id, channel_id, content) VALUES ($1, $2, $3, $4) RETURNING *;",
-        uuid7::uuid7().to_string(),
+    let is_bot =
+        AccountFlags::from_bits_retain(account.flags.unwrap_or(0)).contains(AccountFlags::BOT);
+    if !model.components.is_empty() && (!is_bot || verify_components(&model.components).is_err()) {
+        return Err(OVTError::InvalidComponents.to_resp());
+    }
+    if !model.embeds.is_empty() && (!is_bot || verify_embeds(&model.embeds).is_err()) {
+        return Err(OVTError::InvalidEmbeds.to_resp());
+    }
+
+    // categories hold channels, and forums only take messages through their posts
+    if matches!(
+        ChannelType::try_from(channel.r#type),
+        Ok(ChannelType::Category | ChannelType::Forum)
+    ) {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let referenced_message = if let Some(reference) = &model.reference {
+        Some(
+            get_message(&state.pg, reference.message_id.clone(), &channel.id)
+                .await
+                .map_err(|_| OVTError::InvalidMessageReference.to_resp())?,
+        )
+    } else {
+        None
+    };
+
+    let mut mentions = resolve_mentions(&state.pg, &actor, &guild, &model.content).await?;
+    // replying with a notification mentions the author of the referenced message
+    if let (Some(reference), Some(referenced)) = (&model.reference, &referenced_message) {
+        if let Some(author_id) = &referenced.author_id {
+            if reference.notify_author && !mentions.users.contains(author_id) {
+                mentions.users.push(author_id.clone());
+            }
+        }
+    }
+
+    bump_thread_activity(&state.pg, &channel).await?;
+
+    let uploads = store_uploads(&state, &channel.id, uploads).await?;
+    let message = insert_message(
+        &state,
         &actor.id,
         &channel.id,
-        model.content
-    ).fetch_one(&state.pg).await.map_err(|_| OVTError::InternalServerError.to_resp())?;
+        &model,
+        referenced_message.as_ref(),
+        &mentions,
+        &uploads,
+    )
+    .await;
+    let message = match message {
+        Ok(message) => message,
+        Err(err) => {
+            delete_blobs(
+                &state,
+                uploads.into_iter().map(|upload| upload.blob_key).collect(),
+            )
+            .await;
+            return Err(err);
+        }
+    };
 
     publish_guild(&guild.id, Event::MessageCreate(message.clone())).await?;
 
-    Ok(Json(message))
+    if model.embeds.is_empty() {
+        queue_unfurl(&guild.id, &message);
+    }
+
+    for user_id in mentions
+        .users
+        .iter()
+        .filter(|user_id| **user_id != actor.id)
+    {
+        publish_user(user_id, Event::MessageMention(message.clone())).await?;
+    }
+
+    Ok(Json(hydrate_message(&state, message).await?))
 }
 
+/// Edits a message, keeping its previous content as a revision.
 pub async fn modify_guild_channel_message(
     headers: HeaderMap,
     Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
     State(state): State<OVTState>,
-    Json(model): Json<CreateMessage>,
-) -> Result<Json<Message>, (StatusCode, Json<ErrorMessage>)> {
+    Json(model): Json<ModifyMessage>,
+) -> Result<Json<FullMessage>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
     let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
     let guild = Guild::from_id(&state.pg, guild_id)
         .await
         .map_err(|_| OVTError::GuildNotFound.to_resp())?;
-    get_channel(&state.pg, &channel_id, &guild.id).await?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+
+    let mentions = resolve_mentions(&state.pg, &actor, &guild, &model.content).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
 
     let message = sqlx::query_as!(
         Message,
-        "UPDATE messages SET content = $2 WHERE id = $1 AND author_id = $3 RETURNING *;",
+        "SELECT * FROM messages WHERE id = $1 AND channel_id = $2 AND author_id = $3 FOR UPDATE;",
         message_id,
-        model.content,
+        &channel.id,
         &actor.id
     )
-    .fetch_optional(&state.pg)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::MessageNotFound.to_resp())?;
+
+    if message.content == model.content {
+        return Ok(Json(hydrate_message(&state, message).await?));
+    }
+
+    sqlx::query!(
+        "INSERT INTO message_revisions (id, message_id, content) VALUES ($1, $2, $3);",
+        uuid7::uuid7().to_string(),
+        &message.id,
+        &message.content
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let modified_message = sqlx::query_as!(
+        Message,
+        "UPDATE messages SET content = $2, edited_at = now(), mentions = $3, mention_roles = $4, mention_channels = $5, mention_everyone = $6
+        WHERE id = $1 RETURNING *;",
+        &message.id,
+        model.content,
+        &mentions.users,
+        &mentions.roles,
+        &mentions.channels,
+        mentions.everyone
+    )
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| OVTError::InternalServerError.to_resp())?;
 
-    if let Some(msg) = message {
-        publish_guild(&guild.id, Event::MessageModified(msg.clone())).await?;
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::MessageModified(modified_message.clone())).await?;
 
-        Ok(Json(msg))
-    } else {
-        Err(OVTError::MessageNotFound.to_resp())
-    }
+    Ok(Json(hydrate_message(&state, modified_message).await?))
+}
+
+pub async fn get_guild_channel_message_revisions(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<MessageRevision>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let revisions = MessageRevision::from_message(&state.pg, &message.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(revisions))
 }
 
 pub async fn delete_guild_channel_message(
@@ -166,13 +569,7 @@ pub async fn delete_guild_channel_message(
         .await
         .map_err(|_| OVTError::GuildNotFound.to_resp())?;
     get_channel(&state.pg, &channel_id, &guild.id).await?;
-    let message = Message::from_id(&state.pg, message_id)
-        .await
-        .map_err(|_| OVTError::MessageNotFound.to_resp())?;
-
-    if message.channel_id != channel_id {
-        return Err(OVTError::MessageNotFound.to_resp());
-    }
+    let message = get_message(&state.pg, message_id, &channel_id).await?;
 
     // TODO: refactor when roles happen
     let everyone_perms = GuildPermissions::from_bits(guild.permissions.unwrap() as u64).unwrap();
@@ -187,24 +584,173 @@ pub async fn delete_guild_channel_message(
         return Err(OVTError::InvalidPermissions.to_resp());
     }
 
+    let attachments = Attachment::from_messages(&state.pg, std::slice::from_ref(&message.id))
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
     sqlx::query!("DELETE FROM messages WHERE id = $1;", &message.id)
         .execute(&state.pg)
         .await
         .map_err(|_| OVTError::InternalServerError.to_resp())?;
 
+    delete_blobs(
+        &state,
+        attachments
+            .into_iter()
+            .map(|attachment| attachment.blob_key)
+            .collect(),
+    )
+    .await;
+
     publish_guild(&guild.id, Event::MessageDelete(message.clone())).await?;
 
     Ok((StatusCode::NO_CONTENT, "".to_string()))
 }
 
+/// Publishes a message in an announcement channel to every channel following it.
+pub async fn crosspost_guild_channel_message(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Message>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    if channel.r#type != ChannelType::Announcement as i32 {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let required_permissions = if message.author_id.as_ref() == Some(&actor.id) {
+        GuildPermissions::SEND_MESSAGE
+    } else {
+        GuildPermissions::MANAGE_MESSAGES
+    };
+    verify_permissions(&state.pg, &actor, &guild, required_permissions).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let crossposted = MessageFlags::CROSSPOSTED.bits();
+    let published = sqlx::query_as!(
+        Message,
+        "UPDATE messages SET flags = flags | $2 WHERE id = $1 AND flags & $2 = 0 RETURNING *;",
+        &message.id,
+        crossposted
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::MessageAlreadyCrossposted.to_resp())?;
+
+    let followers = sqlx::query_as!(
+        ChannelFollower,
+        "SELECT * FROM channel_followers WHERE channel_id = $1;",
+        &channel.id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let mut crossposts = Vec::with_capacity(followers.len());
+    for follower in followers {
+        let crosspost = sqlx::query_as!(
+            Message,
+            "INSERT INTO messages (id, channel_id, content, flags, source_guild_id, source_channel_id, source_message_id) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+            uuid7::uuid7().to_string(),
+            &follower.target_channel_id,
+            &published.content,
+            MessageFlags::IS_CROSSPOST.bits(),
+            &guild.id,
+            &channel.id,
+            &published.id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+        advance_last_message_id(&mut tx, &follower.target_channel_id, &crosspost.id).await?;
+        crossposts.push((follower.target_guild_id, crosspost));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::MessageModified(published.clone())).await?;
+    for (target_guild_id, crosspost) in crossposts {
+        publish_guild(&target_guild_id, Event::MessageCreate(crosspost)).await?;
+    }
+
+    Ok(Json(published))
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct GetRecentMentionsFilter {
+    #[validate(minimum = 1)]
+    #[validate(maximum = 100)]
+    limit: i64,
+    before: Option<String>,
+}
+
+impl Default for GetRecentMentionsFilter {
+    fn default() -> Self {
+        Self {
+            limit: 25,
+            before: None,
+        }
+    }
+}
+
+pub async fn get_recent_mentions(
+    headers: HeaderMap,
+    Query(filters): Query<GetRecentMentionsFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<FullMessage>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+
+    let messages = Message::mentioning(
+        &state.pg,
+        &actor.id,
+        filters.before.as_deref(),
+        filters.limit,
+    )
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let messages = hydrate_messages(&state, messages).await?;
+
+    Ok(Json(messages))
+}
+
 pub fn router() -> Router<OVTState> {
     Router::<OVTState>::new()
         .route(
             "/guilds/:guild_id/channels/:channel_id/messages",
-            post(create_guild_channel_message).get(get_guild_channel_messages),
+            post(create_guild_channel_message)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_REQUEST_SIZE))
+                .get(get_guild_channel_messages),
         )
         .route(
             "/guilds/:guild_id/channels/:channel_id/messages/:message_id",
             patch(modify_guild_channel_message).delete(delete_guild_channel_message),
         )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/revisions",
+            get(get_guild_channel_message_revisions),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/crosspost",
+            post(crosspost_guild_channel_message),
+        )
+        .route("/users/@me/mentions", get(get_recent_mentions))
 }
@@ -0,0 +1,124 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::{redirect::Policy, Client, ClientBuilder, Url};
+use tokio::net::lookup_host;
+
+/// Longest url stored on a user's behalf.
+pub const MAX_URL_LENGTH: usize = 512;
+
+/// Parses an http or https url of at most [`MAX_URL_LENGTH`] bytes.
+pub fn parse_web_url(url: &str) -> Option<Url> {
+    Url::parse(url)
+        .ok()
+        .filter(|parsed| url.len() <= MAX_URL_LENGTH && matches!(parsed.scheme(), "http" | "https"))
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "this network", shared address space, protocol assignments, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if ip.is_unspecified() || ip.is_loopback() || ip.is_multicast() {
+        return false;
+    }
+    // mapped and compatible addresses reach whatever ipv4 address they carry
+    if let Some(ipv4) = ip.to_ipv4() {
+        return is_public_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    let embedded_ipv4 = |high: u16, low: u16| {
+        Ipv4Addr::new((high >> 8) as u8, high as u8, (low >> 8) as u8, low as u8)
+    };
+    match segments {
+        // NAT64 and 6to4 also carry an ipv4 address
+        [0x64, 0xff9b, 0, 0, 0, 0, high, low] => is_public_ipv4(embedded_ipv4(high, low)),
+        [0x2002, high, low, ..] => is_public_ipv4(embedded_ipv4(high, low)),
+        // documentation
+        [0x2001, 0xdb8, ..] => false,
+        // unique local and link local
+        [first, ..] => first & 0xfe00 != 0xfc00 && first & 0xffc0 != 0xfe80,
+    }
+}
+
+/// Whether `ip` is on the public internet, rather than loopback, a private network or any other
+/// special purpose range.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+/// Resolves the host of `url`, refusing hosts with any address outside the public internet.
+pub async fn resolve(url: &Url, allow_private_networks: bool) -> Option<Vec<SocketAddr>> {
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+
+    let addresses: Vec<SocketAddr> = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => lookup_host((host, port)).await.ok()?.collect(),
+    };
+
+    if addresses.is_empty()
+        || (!allow_private_networks && addresses.iter().any(|address| !is_public_ip(address.ip())))
+    {
+        return None;
+    }
+
+    Some(addresses)
+}
+
+/// Parses a url the server will send requests to, which has to resolve to the public internet.
+pub async fn parse_public_url(url: &str) -> Option<Url> {
+    let url = parse_web_url(url)?;
+    resolve(&url, false).await?;
+    Some(url)
+}
+
+/// A client for requests to `url` which only connects to the addresses [`resolve`] checked, so
+/// its host can't resolve somewhere else by the time it's connected to. Redirects aren't followed,
+/// they'd have to be checked too.
+pub async fn pinned_client(url: &Url, allow_private_networks: bool) -> Option<ClientBuilder> {
+    let addresses = resolve(url, allow_private_networks).await?;
+
+    Some(
+        Client::builder()
+            .redirect(Policy::none())
+            .no_proxy()
+            .resolve_to_addrs(url.host_str()?, &addresses),
+    )
+}
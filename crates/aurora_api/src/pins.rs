@@ -0,0 +1,206 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{
+    guild::Guild,
+    message::{FullMessage, Message},
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::{GuildPermissions, MessageFlags},
+    guilds::verify_permissions,
+    messages::{advance_last_message_id, get_message, hydrate_messages},
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+/// Maximum number of pinned messages in a single channel.
+pub const MAX_CHANNEL_PINS: i64 = 50;
+
+pub async fn get_channel_pins(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<FullMessage>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    let messages = Message::pinned(&state.pg, &channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let messages = hydrate_messages(&state, messages).await?;
+
+    Ok(Json(messages))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PinMessageOptions {
+    /// Whether to post a message announcing the pin.
+    system_message: bool,
+}
+
+pub async fn pin_message(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    Query(options): Query<PinMessageOptions>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    if message.pinned_at.is_some() {
+        return Ok((StatusCode::NO_CONTENT, "".to_string()));
+    }
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // lock the channel so concurrent pins can't go over the cap
+    sqlx::query!(
+        "SELECT id FROM channels WHERE id = $1 FOR UPDATE;",
+        &channel.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let pins = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM messages WHERE channel_id = $1 AND pinned_at IS NOT NULL;",
+        &channel.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .count;
+
+    if pins >= MAX_CHANNEL_PINS {
+        return Err(OVTError::MaximumPinsReached.to_resp());
+    }
+
+    let pinned_message = sqlx::query_as!(
+        Message,
+        "UPDATE messages SET pinned_at = now() WHERE id = $1 AND pinned_at IS NULL RETURNING *;",
+        &message.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // pinned by someone else in the meantime
+    let Some(pinned_message) = pinned_message else {
+        return Ok((StatusCode::NO_CONTENT, "".to_string()));
+    };
+
+    let notice = if options.system_message {
+        let notice = sqlx::query_as!(
+            Message,
+            "INSERT INTO messages (id, author_id, channel_id, content, flags, reference_id) VALUES ($1, $2, $3, '', $4, $5) RETURNING *;",
+            uuid7::uuid7().to_string(),
+            &actor.id,
+            &channel.id,
+            MessageFlags::PIN_NOTICE.bits(),
+            &message.id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+        advance_last_message_id(&mut tx, &channel.id, &notice.id).await?;
+        Some(notice)
+    } else {
+        None
+    };
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ChannelPinsUpdate(pinned_message)).await?;
+    if let Some(notice) = notice {
+        publish_guild(&guild.id, Event::MessageCreate(notice)).await?;
+    }
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub async fn unpin_message(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let unpinned_message = sqlx::query_as!(
+        Message,
+        "UPDATE messages SET pinned_at = NULL WHERE id = $1 AND pinned_at IS NOT NULL RETURNING *;",
+        &message.id
+    )
+    .fetch_optional(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    if let Some(unpinned_message) = unpinned_message {
+        publish_guild(&guild.id, Event::ChannelPinsUpdate(unpinned_message)).await?;
+    }
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/pins",
+            get(get_channel_pins),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/pins/:message_id",
+            put(pin_message).delete(unpin_message),
+        )
+}
@@ -0,0 +1,195 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use aurora_db::{
+    guild::Guild,
+    poll::{FullPoll, Poll, VoteResult},
+    DBError, FromId,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::put,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_valid::Validate;
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    messages::get_message,
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+fn default_poll_duration() -> i32 {
+    24
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreatePollAnswer {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 55)]
+    text: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreatePoll {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 300)]
+    question: String,
+    #[validate(min_items = 1)]
+    #[validate(max_items = 10)]
+    #[validate]
+    answers: Vec<CreatePollAnswer>,
+    /// Whether users can vote for more than one answer.
+    #[serde(default)]
+    allow_multiselect: bool,
+    /// Hours until the poll closes.
+    #[serde(default = "default_poll_duration")]
+    #[validate(minimum = 1)]
+    #[validate(maximum = 768)]
+    duration: i32,
+}
+
+/// Attaches a poll to a message which is being created in `tx`.
+pub async fn insert_poll(
+    tx: &mut PgConnection,
+    message_id: &str,
+    channel_id: &str,
+    poll: &CreatePoll,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    sqlx::query!(
+        "INSERT INTO polls (message_id, guild_id, question, allow_multiselect, expires_at)
+        SELECT $1, guild_id, $3, $4, now() + make_interval(hours => $5) FROM channels WHERE id = $2;",
+        message_id,
+        channel_id,
+        poll.question,
+        poll.allow_multiselect,
+        poll.duration
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let answer_ids: Vec<i32> = (1..=poll.answers.len() as i32).collect();
+    let texts: Vec<String> = poll
+        .answers
+        .iter()
+        .map(|answer| answer.text.clone())
+        .collect();
+    sqlx::query!(
+        "INSERT INTO poll_answers (message_id, answer_id, text) SELECT $1, * FROM UNNEST($2::INTEGER[], $3::TEXT[]);",
+        message_id,
+        &answer_ids,
+        &texts
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(())
+}
+
+/// Closes polls as they run out of time, publishing their final results.
+pub async fn close_expired_polls(db: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(15));
+
+    loop {
+        interval.tick().await;
+
+        let Ok(polls) = Poll::close_expired(&db).await else {
+            continue;
+        };
+
+        for poll in polls {
+            let guild_id = poll.poll.guild_id.clone();
+            let _ = publish_guild(&guild_id, Event::PollEnd(poll)).await;
+        }
+    }
+}
+
+async fn change_vote(
+    headers: HeaderMap,
+    (guild_id, channel_id, message_id, answer_id): (String, String, String, i32),
+    state: OVTState,
+    vote: bool,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let result = if vote {
+        Poll::vote(&state.pg, &message.id, answer_id, &actor.id).await
+    } else {
+        Poll::unvote(&state.pg, &message.id, answer_id, &actor.id).await
+    };
+    match result {
+        Ok(VoteResult::Changed) => {}
+        Ok(VoteResult::Unchanged) => return Ok((StatusCode::NO_CONTENT, "".to_string())),
+        Ok(VoteResult::Closed) => return Err(OVTError::PollClosed.to_resp()),
+        Ok(VoteResult::AnswerNotFound) => return Err(OVTError::PollAnswerNotFound.to_resp()),
+        Err(DBError::RowNotFound) => return Err(OVTError::PollNotFound.to_resp()),
+        Err(DBError::DBErr) => return Err(OVTError::InternalServerError.to_resp()),
+    }
+
+    let poll = FullPoll::from_message(&state.pg, &message.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    publish_guild(&guild.id, Event::PollUpdate(poll)).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+/// Votes for an answer, replacing the user's earlier vote in polls which only allow one.
+pub async fn add_poll_vote(
+    headers: HeaderMap,
+    Path(path): Path<(String, String, String, i32)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    change_vote(headers, path, state, true).await
+}
+
+pub async fn remove_poll_vote(
+    headers: HeaderMap,
+    Path(path): Path<(String, String, String, i32)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    change_vote(headers, path, state, false).await
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new().route(
+        "/guilds/:guild_id/channels/:channel_id/messages/:message_id/poll/answers/:answer_id/@me",
+        put(add_poll_vote).delete(remove_poll_vote),
+    )
+}
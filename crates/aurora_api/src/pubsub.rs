@@ -14,12 +14,28 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use aurora_db::{
-    actor::Actor, channel::Channel, guild::Guild, guild_member::GuildMember, message::Message,
+    actor::Actor,
+    channel::Channel,
+    forum::{ForumPost, GuildTag},
+    guild::Guild,
+    guild_member::GuildMember,
+    message::Message,
+    poll::FullPoll,
+    reaction::Reaction,
+    read_state::ReadState,
+    thread::{Thread, ThreadMember},
+    webhook::Webhook,
 };
+use std::sync::OnceLock;
+
 use axum::{extract::Json, http::StatusCode};
 use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::error::ErrorMessage;
+use crate::{
+    bulk_delete::MessageDeleteBulk, dispatch::GuildEvent, error::ErrorMessage,
+    interactions::InteractionPayload, typing::TypingStart,
+};
 
 #[derive(Serialize, Clone)]
 #[serde(tag = "t", content = "d")]
@@ -32,9 +48,35 @@ pub enum Event {
     MessageCreate(Message),
     MessageModified(Message),
     MessageDelete(Message),
+    MessageDeleteBulk(MessageDeleteBulk),
+    MessageMention(Message),
     ChannelCreate(Channel),
     ChannelModified(Channel),
     ChannelDelete(String),
+    ChannelsReordered(Vec<Channel>),
+    ChannelPinsUpdate(Message),
+    ThreadCreate(Thread),
+    ThreadUpdate(Thread),
+    ThreadMemberAdd(ThreadMember),
+    ThreadMemberRemove(ThreadMember),
+    GuildTagCreate(GuildTag),
+    GuildTagUpdate(GuildTag),
+    GuildTagDelete(String),
+    ForumPostCreate(ForumPost),
+    ForumPostUpdate(ForumPost),
+    ReactionAdd(Reaction),
+    ReactionRemove(Reaction),
+    ReactionRemoveAll(Message),
+    /// A poll's tallies changed.
+    PollUpdate(FullPoll),
+    /// A poll closed, with its final results.
+    PollEnd(FullPoll),
+    MessageAck(ReadState),
+    TypingStart(TypingStart),
+    WebhookCreate(Webhook),
+    WebhookUpdate(Webhook),
+    WebhookDelete(Webhook),
+    InteractionCreate(Box<InteractionPayload>),
 }
 
 pub async fn publish_user(
@@ -44,9 +86,67 @@ pub async fn publish_user(
     Ok(())
 }
 
+/// Guild events which event subscriptions can ask for, typing is too noisy to push anywhere.
+pub const SUBSCRIBABLE_EVENTS: &[&str] = &[
+    "GuildUpdate",
+    "GuildDelete",
+    "MemberJoin",
+    "MemberLeave",
+    "MessageCreate",
+    "MessageModified",
+    "MessageDelete",
+    "MessageDeleteBulk",
+    "ChannelCreate",
+    "ChannelModified",
+    "ChannelDelete",
+    "ChannelsReordered",
+    "ChannelPinsUpdate",
+    "ThreadCreate",
+    "ThreadUpdate",
+    "ThreadMemberAdd",
+    "ThreadMemberRemove",
+    "GuildTagCreate",
+    "GuildTagUpdate",
+    "GuildTagDelete",
+    "ForumPostCreate",
+    "ForumPostUpdate",
+    "ReactionAdd",
+    "ReactionRemove",
+    "ReactionRemoveAll",
+    "PollUpdate",
+    "PollEnd",
+    "WebhookCreate",
+    "WebhookUpdate",
+    "WebhookDelete",
+];
+
+static DISPATCHER: OnceLock<UnboundedSender<GuildEvent>> = OnceLock::new();
+
+/// Hands every guild event to the event subscription dispatcher from now on.
+pub fn set_dispatcher(dispatcher: UnboundedSender<GuildEvent>) {
+    let _ = DISPATCHER.set(dispatcher);
+}
+
 pub async fn publish_guild(
-    _guild_id: &str,
-    _event: Event,
+    guild_id: &str,
+    event: Event,
 ) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if let Some(dispatcher) = DISPATCHER.get() {
+        let event_type = serde_json::to_value(&event)
+            .ok()
+            .and_then(|value| value["t"].as_str().map(str::to_string))
+            .unwrap_or_default();
+
+        if SUBSCRIBABLE_EVENTS.contains(&event_type.as_str()) {
+            if let Ok(payload) = serde_json::to_string(&event) {
+                let _ = dispatcher.send(GuildEvent {
+                    guild_id: guild_id.to_string(),
+                    event_type,
+                    payload,
+                });
+            }
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,69 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Once this many keys are tracked, expired ones get swept on the next hit.
+const SWEEP_THRESHOLD: usize = 4096;
+
+/// Allows a key through `limit` times per fixed window, kept in memory on this node only.
+pub struct RateLimiter<K> {
+    limit: u32,
+    window: Duration,
+    /// When each key's current window started, and how many hits it has had since.
+    windows: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a hit for `key`, or returns how long until it's allowed again.
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    pub fn check_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, (start, _)| now.saturating_duration_since(*start) < self.window);
+        }
+
+        match windows.get_mut(&key) {
+            Some((start, hits)) if now.saturating_duration_since(*start) < self.window => {
+                if *hits >= self.limit {
+                    return Err(self.window - now.saturating_duration_since(*start));
+                }
+                *hits += 1;
+            }
+            _ => {
+                windows.insert(key, (now, 1));
+            }
+        }
+
+        Ok(())
+    }
+}
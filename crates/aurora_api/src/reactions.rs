@@ -0,0 +1,392 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{
+    guild::Guild,
+    reaction::{Reaction, ReactionCount},
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_valid::Validate;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    messages::get_message,
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+/// Maximum number of distinct emojis a single message can be reacted with.
+pub const MAX_MESSAGE_REACTIONS: i64 = 20;
+
+/// Accepts either a unicode emoji or a custom emoji in the form `name:id`.
+fn verify_emoji(emoji: &str) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    let valid = if let Some((name, id)) = emoji.split_once(':') {
+        (2..=32).contains(&name.len())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && (1..=64).contains(&id.len())
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    } else {
+        // keycap emojis contain ascii digits, so only require that something isn't ascii
+        !emoji.is_empty()
+            && emoji.len() <= 32
+            && !emoji.is_ascii()
+            && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(OVTError::InvalidEmoji.to_resp())
+    }
+}
+
+pub async fn get_message_reactions(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ReactionCount>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let counts = ReactionCount::from_message(&state.pg, &message.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(counts))
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct GetReactionsFilter {
+    #[validate(minimum = 1)]
+    #[validate(maximum = 100)]
+    limit: i64,
+    after: Option<String>,
+}
+
+impl Default for GetReactionsFilter {
+    fn default() -> Self {
+        Self {
+            limit: 25,
+            after: None,
+        }
+    }
+}
+
+pub async fn get_message_reactors(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id, emoji)): Path<(String, String, String, String)>,
+    Query(filters): Query<GetReactionsFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Reaction>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+    verify_emoji(&emoji)?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let reactions = Reaction::from_emoji(
+        &state.pg,
+        &message.id,
+        &emoji,
+        filters.after.as_deref(),
+        filters.limit,
+    )
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(reactions))
+}
+
+pub async fn add_message_reaction(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id, emoji)): Path<(String, String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    verify_emoji(&emoji)?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::ADD_REACTIONS).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // lock the message so concurrent new emojis can't go over the cap
+    sqlx::query!(
+        "SELECT id FROM messages WHERE id = $1 FOR UPDATE;",
+        &message.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::MessageNotFound.to_resp())?;
+
+    let reaction = sqlx::query_as!(
+        Reaction,
+        "INSERT INTO message_reactions (message_id, user_id, emoji) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING RETURNING *;",
+        message.id,
+        actor.id,
+        emoji
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // already reacted
+    let Some(reaction) = reaction else {
+        return Ok((StatusCode::NO_CONTENT, "".to_string()));
+    };
+
+    let count = sqlx::query!(
+        "INSERT INTO message_reaction_counts (message_id, emoji, count) VALUES ($1, $2, 1)
+        ON CONFLICT (message_id, emoji) DO UPDATE SET count = message_reaction_counts.count + 1
+        RETURNING count;",
+        message.id,
+        emoji
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .count;
+
+    if count == 1 {
+        let emojis = sqlx::query!(
+            "SELECT COUNT(*) AS \"count!\" FROM message_reaction_counts WHERE message_id = $1;",
+            message.id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?
+        .count;
+
+        if emojis > MAX_MESSAGE_REACTIONS {
+            return Err(OVTError::MaximumReactionsReached.to_resp());
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ReactionAdd(reaction)).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+async fn remove_reaction(
+    state: &OVTState,
+    guild_id: &str,
+    message_id: &str,
+    emoji: &str,
+    user_id: &str,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let reaction = sqlx::query_as!(
+        Reaction,
+        "DELETE FROM message_reactions WHERE message_id = $1 AND emoji = $2 AND user_id = $3 RETURNING *;",
+        message_id,
+        emoji,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let Some(reaction) = reaction else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "UPDATE message_reaction_counts SET count = count - 1 WHERE message_id = $1 AND emoji = $2;",
+        message_id,
+        emoji
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "DELETE FROM message_reaction_counts WHERE message_id = $1 AND emoji = $2 AND count <= 0;",
+        message_id,
+        emoji
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(guild_id, Event::ReactionRemove(reaction)).await?;
+
+    Ok(())
+}
+
+pub async fn remove_own_message_reaction(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id, emoji)): Path<(String, String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    verify_emoji(&emoji)?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::empty()).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    remove_reaction(&state, &guild.id, &message.id, &emoji, &actor.id).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub async fn remove_user_message_reaction(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id, emoji, user_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    verify_emoji(&emoji)?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    remove_reaction(&state, &guild.id, &message.id, &emoji, &user_id).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub async fn clear_message_reactions(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_MESSAGES).await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    sqlx::query!(
+        "DELETE FROM message_reactions WHERE message_id = $1;",
+        message.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    sqlx::query!(
+        "DELETE FROM message_reaction_counts WHERE message_id = $1;",
+        message.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ReactionRemoveAll(message)).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/reactions",
+            get(get_message_reactions).delete(clear_message_reactions),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/reactions/:emoji",
+            get(get_message_reactors),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/reactions/:emoji/@me",
+            put(add_message_reaction).delete(remove_own_message_reaction),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/reactions/:emoji/:user_id",
+            delete(remove_user_message_reaction),
+        )
+}
@@ -0,0 +1,69 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{guild::Guild, read_state::ReadState, FromId};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    messages::get_message,
+    pubsub::{publish_user, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+pub async fn ack_message(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<ReadState>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+    let message = get_message(&state.pg, message_id, &channel.id).await?;
+
+    let read_state = ReadState::ack(&state.pg, &actor.id, &channel.id, &message.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // keeps badges in sync on the user's other sessions
+    publish_user(&actor.id, Event::MessageAck(read_state.clone())).await?;
+
+    Ok(Json(read_state))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new().route(
+        "/guilds/:guild_id/channels/:channel_id/messages/:message_id/ack",
+        post(ack_message),
+    )
+}
@@ -0,0 +1,147 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{
+    guild::Guild,
+    message::{FullMessage, Message, MessageSearch},
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    messages::hydrate_messages,
+    state::OVTState,
+    token::get_user,
+};
+
+/// The smallest uuid7 created at `date`, so time ranges can be matched against message ids.
+pub fn uuid7_floor(date: DateTime<Utc>) -> String {
+    let millis = date.timestamp_millis().max(0);
+    format!(
+        "{:08x}-{:04x}-0000-0000-000000000000",
+        millis >> 16,
+        millis & 0xffff
+    )
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct SearchMessagesFilter {
+    #[validate(max_length = 256)]
+    content: Option<String>,
+    author_id: Option<String>,
+    channel_id: Option<String>,
+    has_attachment: Option<bool>,
+    mentions: Option<String>,
+    min_date: Option<DateTime<Utc>>,
+    max_date: Option<DateTime<Utc>>,
+    #[validate(minimum = 0)]
+    #[validate(maximum = 5000)]
+    offset: i64,
+    #[validate(minimum = 1)]
+    #[validate(maximum = 50)]
+    limit: i64,
+}
+
+impl Default for SearchMessagesFilter {
+    fn default() -> Self {
+        Self {
+            content: None,
+            author_id: None,
+            channel_id: None,
+            has_attachment: None,
+            mentions: None,
+            min_date: None,
+            max_date: None,
+            offset: 0,
+            limit: 25,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MessageSearchResults {
+    total_results: i64,
+    messages: Vec<FullMessage>,
+}
+
+pub async fn search_guild_messages(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    Query(filters): Query<SearchMessagesFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<MessageSearchResults>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    // TODO: narrow down to viewable channels once channels get their own permissions
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+    if let Some(channel_id) = &filters.channel_id {
+        get_channel(&state.pg, channel_id, &guild.id).await?;
+    }
+
+    let search = MessageSearch {
+        query: filters
+            .content
+            .as_deref()
+            .filter(|content| !content.trim().is_empty()),
+        author_id: filters.author_id.as_deref(),
+        channel_id: filters.channel_id.as_deref(),
+        has_attachment: filters.has_attachment,
+        mentions: filters.mentions.as_deref(),
+        min_id: filters.min_date.map(uuid7_floor),
+        max_id: filters.max_date.map(uuid7_floor),
+        offset: filters.offset,
+        limit: filters.limit,
+    };
+    let (total_results, messages) = Message::search(&state.pg, &guild.id, &search)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(MessageSearchResults {
+        total_results,
+        messages: hydrate_messages(&state, messages).await?,
+    }))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new().route(
+        "/guilds/:guild_id/messages/search",
+        get(search_guild_messages),
+    )
+}
@@ -13,10 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::sync::Arc;
+
 use sqlx::PgPool;
 
-#[derive(Debug, Clone)]
+use crate::{ratelimit::RateLimiter, storage::BlobStore};
+
+#[derive(Clone)]
 pub struct OVTState {
     pub pg: PgPool,
     pub key: String,
+    /// Signs attachment download urls, see [`download_key`](crate::storage::download_key).
+    pub download_key: Vec<u8>,
+    pub blobs: Arc<dyn BlobStore>,
+    /// Keyed by user and channel id.
+    pub typing: Arc<RateLimiter<(String, String)>>,
+    /// Keyed by webhook id.
+    pub webhooks: Arc<RateLimiter<String>>,
 }
@@ -0,0 +1,348 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{env, path::PathBuf, sync::Arc};
+
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug)]
+pub enum BlobError {
+    NotFound,
+    InvalidKey,
+    Backend(String),
+}
+
+impl std::fmt::Display for BlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "blob not found"),
+            Self::InvalidKey => write!(f, "invalid blob key"),
+            Self::Backend(err) => write!(f, "blob store error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BlobError {}
+
+/// Somewhere to keep uploaded files, addressed by `/` separated keys.
+pub trait BlobStore: Send + Sync {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), BlobError>>;
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobError>>;
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobError>>;
+}
+
+/// Picks a blob store from `BLOB_STORE`, which is either `local` (the default) or `s3`.
+pub fn blob_store_from_env() -> Arc<dyn BlobStore> {
+    match env::var("BLOB_STORE").as_deref() {
+        Ok("s3") => Arc::new(S3BlobStore::new(
+            &env::var("S3_ENDPOINT").unwrap(),
+            env::var("S3_BUCKET").unwrap(),
+            env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            env::var("S3_ACCESS_KEY").unwrap(),
+            env::var("S3_SECRET_KEY").unwrap(),
+        )),
+        _ => Arc::new(LocalBlobStore::new(
+            env::var("BLOB_STORE_PATH").unwrap_or_else(|_| "./blobs".to_string()),
+        )),
+    }
+}
+
+/// Keys are generated by the API, but never let one escape the store.
+fn verify_key(key: &str) -> Result<(), BlobError> {
+    if key.is_empty()
+        || key
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        Err(BlobError::InvalidKey)
+    } else {
+        Ok(())
+    }
+}
+
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), BlobError>> {
+        Box::pin(async move {
+            verify_key(key)?;
+            let path = self.root.join(key);
+
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|err| BlobError::Backend(err.to_string()))?;
+            }
+            tokio::fs::write(path, data)
+                .await
+                .map_err(|err| BlobError::Backend(err.to_string()))
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobError>> {
+        Box::pin(async move {
+            verify_key(key)?;
+
+            tokio::fs::read(self.root.join(key))
+                .await
+                .map_err(|err| match err.kind() {
+                    std::io::ErrorKind::NotFound => BlobError::NotFound,
+                    _ => BlobError::Backend(err.to_string()),
+                })
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobError>> {
+        Box::pin(async move {
+            verify_key(key)?;
+
+            match tokio::fs::remove_file(self.root.join(key)).await {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(BlobError::Backend(err.to_string())),
+            }
+        })
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything but unreserved characters, as SigV4 expects.
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// A bucket on any S3-compatible server, using path-style addressing and SigV4 signing.
+pub struct S3BlobStore {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3BlobStore {
+    pub fn new(
+        endpoint: &str,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+    ) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: Url::parse(endpoint).expect("S3 endpoint must be a valid url"),
+            bucket,
+            region,
+            access_key,
+            secret_key,
+        }
+    }
+
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response, BlobError> {
+        verify_key(key)?;
+
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&self.bucket, false),
+            uri_encode(key, true)
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            _ => return Err(BlobError::Backend("S3 endpoint has no host".to_string())),
+        };
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut signing_key = hmac_sha256(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}",
+                    self.access_key
+                ),
+            );
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+
+        request
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| BlobError::Backend(err.to_string()))
+    }
+}
+
+impl BlobStore for S3BlobStore {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), BlobError>> {
+        Box::pin(async move {
+            let response = self
+                .send(reqwest::Method::PUT, key, data, Some(content_type))
+                .await?;
+
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(BlobError::Backend(format!(
+                    "PUT {key} returned {}",
+                    response.status()
+                )))
+            }
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobError>> {
+        Box::pin(async move {
+            let response = self
+                .send(reqwest::Method::GET, key, Vec::new(), None)
+                .await?;
+
+            match response.status() {
+                status if status.is_success() => Ok(response
+                    .bytes()
+                    .await
+                    .map_err(|err| BlobError::Backend(err.to_string()))?
+                    .to_vec()),
+                StatusCode::NOT_FOUND => Err(BlobError::NotFound),
+                status => Err(BlobError::Backend(format!("GET {key} returned {status}"))),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobError>> {
+        Box::pin(async move {
+            let response = self
+                .send(reqwest::Method::DELETE, key, Vec::new(), None)
+                .await?;
+
+            // S3 answers deletes of missing keys with 204 too
+            if response.status().is_success() || response.status() == StatusCode::NOT_FOUND {
+                Ok(())
+            } else {
+                Err(BlobError::Backend(format!(
+                    "DELETE {key} returned {}",
+                    response.status()
+                )))
+            }
+        })
+    }
+}
+
+/// The key download urls are signed with, derived from the token secret so the same key is never
+/// used for both.
+pub fn download_key(secret: &str) -> Vec<u8> {
+    hmac_sha256(secret.as_bytes(), b"aurora attachment downloads")
+}
+
+/// The signature for downloading `path` until the unix timestamp `expires`.
+pub fn sign_download(key: &[u8], path: &str, expires: i64) -> String {
+    hex::encode(hmac_sha256(
+        key,
+        format!("download:{path}:{expires}").as_bytes(),
+    ))
+}
+
+/// Checks a download signature in constant time, rejecting it once it has expired.
+pub fn verify_download(key: &[u8], path: &str, expires: i64, signature: &str) -> bool {
+    if expires < Utc::now().timestamp() {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac takes keys of any size");
+    mac.update(format!("download:{path}:{expires}").as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
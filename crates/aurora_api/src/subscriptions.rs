@@ -0,0 +1,318 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use aurora_db::{
+    event_subscription::{EventDelivery, EventSubscription},
+    guild::Guild,
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, patch},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::{
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    net::parse_public_url,
+    pubsub::SUBSCRIBABLE_EVENTS,
+    state::OVTState,
+    token::get_user,
+};
+
+/// Maximum number of event subscriptions in a single guild.
+pub const MAX_GUILD_SUBSCRIPTIONS: i64 = 10;
+
+/// Deliveries are only ever sent to hosts on the public internet, this is checked again on every
+/// attempt since where a host resolves can change.
+async fn verify_subscription(
+    url: Option<&str>,
+    event_types: Option<&[String]>,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if let Some(url) = url {
+        parse_public_url(url)
+            .await
+            .ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+
+    if let Some(event_types) = event_types {
+        if !event_types
+            .iter()
+            .all(|event_type| SUBSCRIBABLE_EVENTS.contains(&event_type.as_str()))
+        {
+            return Err(OVTError::InvalidBody.to_resp());
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_subscription(
+    db: &sqlx::PgPool,
+    subscription_id: String,
+    guild_id: &str,
+) -> Result<EventSubscription, (StatusCode, Json<ErrorMessage>)> {
+    let subscription = EventSubscription::from_id(db, subscription_id)
+        .await
+        .map_err(|_| OVTError::SubscriptionNotFound.to_resp())?;
+
+    if subscription.guild_id != guild_id {
+        return Err(OVTError::SubscriptionNotFound.to_resp());
+    }
+
+    Ok(subscription)
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateSubscription {
+    url: String,
+    #[validate(min_items = 1)]
+    #[validate(max_items = 50)]
+    #[validate(unique_items)]
+    event_types: Vec<String>,
+}
+
+/// A freshly created subscription, the only time its signing secret is ever shown.
+#[derive(Serialize)]
+pub struct CreatedSubscription {
+    #[serde(flatten)]
+    subscription: EventSubscription,
+    secret: String,
+}
+
+pub async fn create_subscription(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateSubscription>,
+) -> Result<Json<CreatedSubscription>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    verify_subscription(Some(&model.url), Some(&model.event_types)).await?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // locks the guild so concurrent creations can't go over the limit
+    sqlx::query!("SELECT id FROM guilds WHERE id = $1 FOR UPDATE;", &guild.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let subscriptions = sqlx::query_scalar!(
+        "SELECT COUNT(*) AS \"count!\" FROM event_subscriptions WHERE guild_id = $1;",
+        &guild.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    if subscriptions >= MAX_GUILD_SUBSCRIPTIONS {
+        return Err(OVTError::MaximumSubscriptionsReached.to_resp());
+    }
+
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let secret = hex::encode(secret);
+
+    let subscription = sqlx::query_as!(
+        EventSubscription,
+        "INSERT INTO event_subscriptions (id, guild_id, creator_id, url, secret, event_types) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &guild.id,
+        &actor.id,
+        model.url,
+        &secret,
+        &model.event_types
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(CreatedSubscription {
+        subscription,
+        secret,
+    }))
+}
+
+pub async fn get_subscriptions(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<EventSubscription>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+
+    let subscriptions = EventSubscription::from_guild(&state.pg, &guild.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(subscriptions))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ModifySubscription {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    #[validate(min_items = 1)]
+    #[validate(max_items = 50)]
+    #[validate(unique_items)]
+    event_types: Option<Vec<String>>,
+    /// Re-enabling a subscription also forgets its past failures.
+    #[serde(default)]
+    enabled: Option<bool>,
+}
+
+pub async fn modify_subscription(
+    headers: HeaderMap,
+    Path((guild_id, subscription_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<ModifySubscription>,
+) -> Result<Json<EventSubscription>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    verify_subscription(model.url.as_deref(), model.event_types.as_deref()).await?;
+    let subscription = get_subscription(&state.pg, subscription_id, &guild.id).await?;
+
+    let modified_subscription = sqlx::query_as!(
+        EventSubscription,
+        "UPDATE event_subscriptions SET url = COALESCE($1, url), event_types = COALESCE($2, event_types), enabled = COALESCE($3, enabled), consecutive_failures = CASE WHEN $3 THEN 0 ELSE consecutive_failures END WHERE id = $4 RETURNING *;",
+        model.url,
+        model.event_types.as_deref(),
+        model.enabled,
+        &subscription.id
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(modified_subscription))
+}
+
+pub async fn delete_subscription(
+    headers: HeaderMap,
+    Path((guild_id, subscription_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    let subscription = get_subscription(&state.pg, subscription_id, &guild.id).await?;
+
+    sqlx::query!(
+        "DELETE FROM event_subscriptions WHERE id = $1;",
+        &subscription.id
+    )
+    .execute(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+#[derive(Deserialize, Validate)]
+#[serde(default)]
+pub struct GetDeliveriesFilter {
+    before: Option<String>,
+    #[validate(minimum = 1)]
+    #[validate(maximum = 100)]
+    limit: i64,
+}
+
+impl Default for GetDeliveriesFilter {
+    fn default() -> Self {
+        Self {
+            before: None,
+            limit: 25,
+        }
+    }
+}
+
+/// The subscription's delivery log, newest first.
+pub async fn get_subscription_deliveries(
+    headers: HeaderMap,
+    Path((guild_id, subscription_id)): Path<(String, String)>,
+    Query(filters): Query<GetDeliveriesFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<EventDelivery>>, (StatusCode, Json<ErrorMessage>)> {
+    filters
+        .validate()
+        .map_err(|_| OVTError::InvalidQuery.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    let subscription = get_subscription(&state.pg, subscription_id, &guild.id).await?;
+
+    let deliveries = EventDelivery::from_subscription(
+        &state.pg,
+        &subscription.id,
+        filters.before.as_deref(),
+        filters.limit,
+    )
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(deliveries))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/subscriptions",
+            get(get_subscriptions).post(create_subscription),
+        )
+        .route(
+            "/guilds/:guild_id/subscriptions/:subscription_id",
+            patch(modify_subscription).delete(delete_subscription),
+        )
+        .route(
+            "/guilds/:guild_id/subscriptions/:subscription_id/deliveries",
+            get(get_subscription_deliveries),
+        )
+}
@@ -0,0 +1,584 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use aurora_db::{
+    actor::Actor,
+    channel::{Channel, ChannelType},
+    guild::Guild,
+    message::Message,
+    thread::{Thread, ThreadMember, ThreadMetadata},
+    FromId,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_valid::Validate;
+use sqlx::PgPool;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+pub async fn get_thread(
+    db: &PgPool,
+    thread_id: &str,
+    guild_id: &str,
+) -> Result<Thread, (StatusCode, Json<ErrorMessage>)> {
+    let channel = get_channel(db, thread_id, guild_id)
+        .await
+        .map_err(|_| OVTError::ThreadNotFound.to_resp())?;
+
+    if channel.r#type != ChannelType::Thread as i32 {
+        return Err(OVTError::ThreadNotFound.to_resp());
+    }
+
+    let thread_metadata = ThreadMetadata::from_id(db, channel.id.clone())
+        .await
+        .map_err(|_| OVTError::ThreadNotFound.to_resp())?;
+
+    Ok(Thread {
+        channel,
+        thread_metadata,
+    })
+}
+
+/// Threads don't carry permissions of their own, they're resolved against the parent channel.
+pub async fn verify_thread_permissions(
+    db: &PgPool,
+    user: &Actor,
+    guild: &Guild,
+    thread: &Thread,
+    required_permissions: GuildPermissions,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    let parent_id = thread
+        .channel
+        .parent_id
+        .as_deref()
+        .ok_or_else(|| OVTError::ChannelNotFound.to_resp())?;
+    get_channel(db, parent_id, &guild.id).await?;
+
+    verify_permissions(db, user, guild, required_permissions).await
+}
+
+/// Thread owners can always manage their own thread.
+pub async fn verify_thread_manager(
+    db: &PgPool,
+    user: &Actor,
+    guild: &Guild,
+    thread: &Thread,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if thread.thread_metadata.owner_id == user.id {
+        verify_thread_permissions(db, user, guild, thread, GuildPermissions::empty()).await
+    } else {
+        verify_thread_permissions(db, user, guild, thread, GuildPermissions::MANAGE_CHANNELS).await
+    }
+}
+
+/// Rejects messages sent to archived threads and keeps active ones from auto-archiving.
+/// Does nothing for channels that aren't threads.
+pub async fn bump_thread_activity(
+    db: &PgPool,
+    channel: &Channel,
+) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if channel.r#type != ChannelType::Thread as i32 {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "UPDATE thread_metadata SET last_activity_at = now() WHERE id = $1 AND NOT archived RETURNING id;",
+        &channel.id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::ThreadArchived.to_resp())?;
+
+    Ok(())
+}
+
+/// Archives every thread which has gone longer than its `auto_archive_duration` without activity.
+pub async fn archive_inactive_threads(db: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+
+        let Ok(archived) = sqlx::query_as!(
+            ThreadMetadata,
+            "UPDATE thread_metadata SET archived = true, archived_at = now() WHERE NOT archived AND last_activity_at + make_interval(mins => auto_archive_duration) < now() RETURNING *;"
+        )
+        .fetch_all(&db)
+        .await
+        else {
+            continue;
+        };
+
+        for thread_metadata in archived {
+            let Ok(channel) = Channel::from_id(&db, thread_metadata.id.clone()).await else {
+                continue;
+            };
+
+            if let Some(guild_id) = channel.guild_id.clone() {
+                let _ = publish_guild(
+                    &guild_id,
+                    Event::ThreadUpdate(Thread {
+                        channel,
+                        thread_metadata,
+                    }),
+                )
+                .await;
+            }
+        }
+    }
+}
+
+fn default_auto_archive_duration() -> i32 {
+    1440
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateThread {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 100)]
+    name: String,
+    /// Minutes of inactivity before the thread is archived.
+    #[serde(default = "default_auto_archive_duration")]
+    #[validate(enumerate = [60, 1440, 4320, 10080])]
+    auto_archive_duration: i32,
+}
+
+pub async fn create_message_thread(
+    headers: HeaderMap,
+    Path((guild_id, channel_id, message_id)): Path<(String, String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateThread>,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let parent = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
+
+    if !matches!(
+        ChannelType::try_from(parent.r#type),
+        Ok(ChannelType::Text | ChannelType::Announcement)
+    ) {
+        return Err(OVTError::InvalidChannelType.to_resp());
+    }
+
+    let message = Message::from_id(&state.pg, message_id)
+        .await
+        .map_err(|_| OVTError::MessageNotFound.to_resp())?;
+
+    if message.channel_id != parent.id {
+        return Err(OVTError::MessageNotFound.to_resp());
+    }
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    // lock the message so concurrent creates can't both start a thread from it
+    sqlx::query!(
+        "SELECT id FROM messages WHERE id = $1 FOR UPDATE;",
+        &message.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?
+    .ok_or_else(|| OVTError::MessageNotFound.to_resp())?;
+
+    let existing = sqlx::query!(
+        "SELECT id FROM thread_metadata WHERE message_id = $1;",
+        &message.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    if existing.is_some() {
+        return Err(OVTError::ThreadAlreadyExists.to_resp());
+    }
+
+    let channel = sqlx::query_as!(
+        Channel,
+        "INSERT INTO channels (id, name, guild_id, position, type, parent_id) VALUES ($1, $2, $3, 0, $4, $5) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        model.name.trim(),
+        &guild.id,
+        ChannelType::Thread as i32,
+        &parent.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let thread_metadata = sqlx::query_as!(
+        ThreadMetadata,
+        "INSERT INTO thread_metadata (id, message_id, owner_id, auto_archive_duration) VALUES ($1, $2, $3, $4) RETURNING *;",
+        &channel.id,
+        &message.id,
+        &actor.id,
+        model.auto_archive_duration
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let member = sqlx::query_as!(
+        ThreadMember,
+        "INSERT INTO thread_members (thread_id, user_id) VALUES ($1, $2) RETURNING *;",
+        &channel.id,
+        &actor.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let thread = Thread {
+        channel,
+        thread_metadata,
+    };
+
+    publish_guild(&guild.id, Event::ThreadCreate(thread.clone())).await?;
+    publish_guild(&guild.id, Event::ThreadMemberAdd(member)).await?;
+
+    Ok(Json(thread))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct GetChannelThreadsFilter {
+    archived: bool,
+}
+
+pub async fn get_channel_threads(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    Query(filters): Query<GetChannelThreadsFilter>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Thread>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let parent = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    let threads = Thread::from_parent(&state.pg, &parent.id, filters.archived)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(threads))
+}
+
+pub async fn get_guild_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        &thread,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    Ok(Json(thread))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ModifyThread {
+    #[serde(default)]
+    #[validate(min_length = 1)]
+    #[validate(max_length = 100)]
+    name: Option<String>,
+    #[serde(default)]
+    #[validate(enumerate = [60, 1440, 4320, 10080])]
+    auto_archive_duration: Option<i32>,
+}
+
+pub async fn modify_guild_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<ModifyThread>,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_manager(&state.pg, &actor, &guild, &thread).await?;
+
+    if thread.thread_metadata.archived {
+        return Err(OVTError::ThreadArchived.to_resp());
+    }
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let channel = sqlx::query_as!(
+        Channel,
+        "UPDATE channels SET name = COALESCE($2, name) WHERE id = $1 RETURNING *;",
+        &thread.channel.id,
+        model.name.as_deref().map(str::trim)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    let thread_metadata = sqlx::query_as!(
+        ThreadMetadata,
+        "UPDATE thread_metadata SET auto_archive_duration = COALESCE($2, auto_archive_duration) WHERE id = $1 RETURNING *;",
+        &thread.channel.id,
+        model.auto_archive_duration
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let modified_thread = Thread {
+        channel,
+        thread_metadata,
+    };
+
+    publish_guild(&guild.id, Event::ThreadUpdate(modified_thread.clone())).await?;
+
+    Ok(Json(modified_thread))
+}
+
+async fn set_thread_archived(
+    headers: HeaderMap,
+    guild_id: String,
+    thread_id: String,
+    state: OVTState,
+    archived: bool,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_manager(&state.pg, &actor, &guild, &thread).await?;
+
+    // unarchiving counts as activity, otherwise the thread would be archived again right away
+    let thread_metadata = sqlx::query_as!(
+        ThreadMetadata,
+        "UPDATE thread_metadata SET archived = $2, archived_at = CASE WHEN $2 THEN now() END, last_activity_at = CASE WHEN $2 THEN last_activity_at ELSE now() END WHERE id = $1 RETURNING *;",
+        &thread.channel.id,
+        archived
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let modified_thread = Thread {
+        channel: thread.channel,
+        thread_metadata,
+    };
+
+    publish_guild(&guild.id, Event::ThreadUpdate(modified_thread.clone())).await?;
+
+    Ok(Json(modified_thread))
+}
+
+pub async fn archive_guild_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    set_thread_archived(headers, guild_id, thread_id, state, true).await
+}
+
+pub async fn unarchive_guild_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Thread>, (StatusCode, Json<ErrorMessage>)> {
+    set_thread_archived(headers, guild_id, thread_id, state, false).await
+}
+
+pub async fn get_thread_members(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<ThreadMember>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        &thread,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    let members = ThreadMember::from_thread(&state.pg, &thread.channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(members))
+}
+
+pub async fn join_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<ThreadMember>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+    verify_thread_permissions(
+        &state.pg,
+        &actor,
+        &guild,
+        &thread,
+        GuildPermissions::VIEW_MESSAGE_HISTORY,
+    )
+    .await?;
+
+    if thread.thread_metadata.archived {
+        return Err(OVTError::ThreadArchived.to_resp());
+    }
+
+    if let Ok(member) = ThreadMember::from_id(&state.pg, (&thread.channel.id, &actor.id)).await {
+        return Ok(Json(member));
+    }
+
+    let member = sqlx::query_as!(
+        ThreadMember,
+        "INSERT INTO thread_members (thread_id, user_id) VALUES ($1, $2) RETURNING *;",
+        &thread.channel.id,
+        &actor.id
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::ThreadMemberAdd(member.clone())).await?;
+
+    Ok(Json(member))
+}
+
+pub async fn leave_thread(
+    headers: HeaderMap,
+    Path((guild_id, thread_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let thread = get_thread(&state.pg, &thread_id, &guild.id).await?;
+
+    let member = sqlx::query_as!(
+        ThreadMember,
+        "DELETE FROM thread_members WHERE thread_id = $1 AND user_id = $2 RETURNING *;",
+        &thread.channel.id,
+        &actor.id
+    )
+    .fetch_optional(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    if let Some(mem) = member {
+        publish_guild(&guild.id, Event::ThreadMemberRemove(mem)).await?;
+    }
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/messages/:message_id/threads",
+            post(create_message_thread),
+        )
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/threads",
+            get(get_channel_threads),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id",
+            get(get_guild_thread).patch(modify_guild_thread),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/archive",
+            post(archive_guild_thread),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/unarchive",
+            post(unarchive_guild_thread),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/members",
+            get(get_thread_members),
+        )
+        .route(
+            "/guilds/:guild_id/threads/:thread_id/members/@me",
+            put(join_thread).delete(leave_thread),
+        )
+}
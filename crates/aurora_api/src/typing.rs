@@ -0,0 +1,92 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use aurora_db::{guild::Guild, FromId};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    channels::get_channel,
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+/// How long clients show someone as typing after a `TypingStart`.
+pub const TYPING_DURATION: Duration = Duration::from_secs(10);
+/// Minimum time between typing events from the same user in the same channel.
+pub const TYPING_RATE_LIMIT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone)]
+pub struct TypingStart {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub user_id: String,
+    pub timestamp: DateTime<Utc>,
+    /// When clients should stop showing the indicator unless another event arrives.
+    pub expires_at: DateTime<Utc>,
+}
+
+// TODO: DM and group channels once they exist
+pub async fn trigger_typing(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::SEND_MESSAGE).await?;
+
+    state
+        .typing
+        .check((actor.id.clone(), channel.id.clone()))
+        .map_err(|_| OVTError::RateLimited.to_resp())?;
+
+    let timestamp = Utc::now();
+    publish_guild(
+        &guild.id,
+        Event::TypingStart(TypingStart {
+            guild_id: guild.id.clone(),
+            channel_id: channel.id,
+            user_id: actor.id,
+            timestamp,
+            expires_at: timestamp + TYPING_DURATION,
+        }),
+    )
+    .await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new().route(
+        "/guilds/:guild_id/channels/:channel_id/typing",
+        post(trigger_typing),
+    )
+}
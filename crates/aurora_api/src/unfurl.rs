@@ -0,0 +1,513 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use aurora_db::{
+    embed::{Embed, EmbedAuthor, EmbedFooter, EmbedMedia},
+    message::Message,
+};
+use regex::Regex;
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE, LOCATION},
+    Url,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::sync::{mpsc::UnboundedReceiver, Semaphore};
+
+use crate::{
+    embeds::{stored_embeds, verify_embeds},
+    net::pinned_client,
+};
+
+/// Only the first few links in a message get a preview.
+pub const MAX_UNFURLS_PER_MESSAGE: usize = 5;
+pub const MAX_CONCURRENT_UNFURLS: usize = 8;
+/// How long a fetched preview, or the lack of one, is reused for.
+pub const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; Aurora link previews)";
+
+const MAX_PREVIEW_TITLE_LENGTH: usize = 256;
+const MAX_PREVIEW_DESCRIPTION_LENGTH: usize = 350;
+const MAX_PREVIEW_NAME_LENGTH: usize = 256;
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(<)?(https?://[^\s<>]+)(>)?").unwrap());
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\s[^>]*>").unwrap());
+static TITLE_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)([a-zA-Z_:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|[a-zA-Z]+);").unwrap());
+
+/// Limits on what the unfurler fetches.
+#[derive(Clone)]
+pub struct UnfurlConfig {
+    /// Covers the whole fetch of a page, from resolving its host to reading its body.
+    pub timeout: Duration,
+    /// Bodies are cut off after this many bytes, which is plenty to reach a page's `<head>`.
+    pub max_body_size: usize,
+    pub max_redirects: usize,
+    /// Only for tests against local servers, previews must never reach internal services.
+    pub allow_private_networks: bool,
+}
+
+impl Default for UnfurlConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_body_size: 1024 * 1024,
+            max_redirects: 3,
+            allow_private_networks: false,
+        }
+    }
+}
+
+/// The links in `content` worth previewing, links wrapped in `<>` are left alone.
+pub fn extract_urls(content: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+
+    for captures in URL.captures_iter(content) {
+        if captures.get(1).is_some() && captures.get(3).is_some() {
+            continue;
+        }
+
+        let mut url = &captures[2];
+        // trailing punctuation usually ends the sentence rather than the link
+        loop {
+            let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"']);
+            let trimmed = if trimmed.ends_with(')')
+                && trimmed.matches('(').count() < trimmed.matches(')').count()
+            {
+                &trimmed[..trimmed.len() - 1]
+            } else {
+                trimmed
+            };
+            if trimmed == url {
+                break;
+            }
+            url = trimmed;
+        }
+
+        if Url::parse(url).is_ok() && !urls.iter().any(|seen| seen == url) {
+            urls.push(url.to_string());
+        }
+        if urls.len() == MAX_UNFURLS_PER_MESSAGE {
+            break;
+        }
+    }
+
+    urls
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |captures: &regex::Captures| {
+            let entity = &captures[1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                }
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            decoded.map_or_else(|| captures[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Decodes entities and collapses whitespace, dropping texts which end up empty.
+fn clean_text(text: &str) -> Option<String> {
+    let text = decode_entities(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn attributes(tag: &str) -> HashMap<String, String> {
+    ATTRIBUTE
+        .captures_iter(tag)
+        .map(|captures| {
+            let value = captures
+                .get(2)
+                .or(captures.get(3))
+                .or(captures.get(4))
+                .map_or("", |value| value.as_str());
+            (captures[1].to_ascii_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+/// What a page says about itself through OpenGraph and plain HTML tags.
+#[derive(Default, Debug, PartialEq)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub image: Option<String>,
+    pub color: Option<u32>,
+    /// Where the page's oEmbed data can be fetched from.
+    pub oembed_url: Option<String>,
+}
+
+/// Reads a page's metadata, resolving relative urls against `base`.
+pub fn parse_metadata(html: &str, base: &Url) -> PageMetadata {
+    let mut meta = HashMap::new();
+    for tag in META_TAG.find_iter(html) {
+        let mut attributes = attributes(tag.as_str());
+        let Some(key) = attributes
+            .remove("property")
+            .or_else(|| attributes.remove("name"))
+        else {
+            continue;
+        };
+        if let Some(content) = attributes.remove("content") {
+            // the first of repeated tags wins
+            meta.entry(key.to_ascii_lowercase()).or_insert(content);
+        }
+    }
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| meta.get(*key).and_then(|value| clean_text(value)))
+    };
+    let absolute = |url: String| {
+        base.join(&url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(String::from)
+    };
+
+    let oembed_url = LINK_TAG.find_iter(html).find_map(|tag| {
+        let attributes = attributes(tag.as_str());
+        let is_oembed = attributes.get("rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("alternate"))
+        }) && attributes
+            .get("type")
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("application/json+oembed"));
+        is_oembed
+            .then(|| attributes.get("href").map(|href| decode_entities(href)))
+            .flatten()
+            .and_then(absolute)
+    });
+
+    PageMetadata {
+        title: first(&["og:title", "twitter:title"]).or_else(|| {
+            TITLE_TAG
+                .captures(html)
+                .and_then(|captures| clean_text(&captures[1]))
+        }),
+        description: first(&["og:description", "twitter:description", "description"]),
+        site_name: first(&["og:site_name"]),
+        image: first(&[
+            "og:image:secure_url",
+            "og:image:url",
+            "og:image",
+            "twitter:image",
+        ])
+        .and_then(absolute),
+        color: first(&["theme-color"]).and_then(|color| {
+            let hex = color.strip_prefix('#')?;
+            (hex.len() == 6)
+                .then(|| u32::from_str_radix(hex, 16).ok())
+                .flatten()
+        }),
+        oembed_url,
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct OEmbed {
+    title: Option<String>,
+    author_name: Option<String>,
+    author_url: Option<String>,
+    provider_name: Option<String>,
+    thumbnail_url: Option<String>,
+}
+
+/// Turns what was found about a page into the embed shown below messages linking to it.
+fn build_embed(url: &Url, page: PageMetadata, oembed: OEmbed) -> Option<Embed> {
+    let web_url = |url: String| {
+        Url::parse(&url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .map(String::from)
+    };
+
+    let title = page
+        .title
+        .or(oembed.title.and_then(|title| clean_text(&title)));
+    if title.is_none() && page.description.is_none() {
+        return None;
+    }
+
+    let embed = Embed {
+        title: title.map(|title| truncate(title, MAX_PREVIEW_TITLE_LENGTH)),
+        description: page
+            .description
+            .map(|description| truncate(description, MAX_PREVIEW_DESCRIPTION_LENGTH)),
+        url: Some(url.to_string()),
+        color: page.color,
+        footer: page
+            .site_name
+            .or(oembed.provider_name.and_then(|name| clean_text(&name)))
+            .map(|text| EmbedFooter {
+                text: truncate(text, MAX_PREVIEW_NAME_LENGTH),
+                icon_url: None,
+            }),
+        author: oembed
+            .author_name
+            .and_then(|name| clean_text(&name))
+            .map(|name| EmbedAuthor {
+                name: truncate(name, MAX_PREVIEW_NAME_LENGTH),
+                url: oembed.author_url.and_then(web_url),
+                icon_url: None,
+            }),
+        thumbnail: page
+            .image
+            .or(oembed.thumbnail_url.and_then(web_url))
+            .map(|url| EmbedMedia { url }),
+        ..Default::default()
+    };
+
+    verify_embeds(std::slice::from_ref(&embed))
+        .is_ok()
+        .then_some(embed)
+}
+
+/// Which message to unfurl the links of.
+pub struct UnfurlJob {
+    pub guild_id: String,
+    pub message_id: String,
+}
+
+/// Fetches link previews, caching them by url.
+pub struct Unfurler {
+    db: PgPool,
+    config: UnfurlConfig,
+}
+
+impl Unfurler {
+    pub fn new(db: PgPool, config: UnfurlConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Fetches up to `max_body_size` bytes of `url`, following redirects by hand so every hop
+    /// is checked, and connecting only to the addresses that were checked.
+    async fn fetch(&self, mut url: Url, accept: &str) -> Option<(Url, String, String)> {
+        for _ in 0..=self.config.max_redirects {
+            if !matches!(url.scheme(), "http" | "https") {
+                return None;
+            }
+            let client = pinned_client(&url, self.config.allow_private_networks)
+                .await?
+                .timeout(self.config.timeout)
+                .user_agent(USER_AGENT)
+                .build()
+                .ok()?;
+            let mut response = client
+                .get(url.clone())
+                .header(ACCEPT, accept)
+                .send()
+                .await
+                .ok()?;
+
+            if response.status().is_redirection() {
+                let location = response.headers().get(LOCATION)?.to_str().ok()?;
+                url = url.join(location).ok()?;
+                continue;
+            }
+            if !response.status().is_success() {
+                return None;
+            }
+
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await.ok()? {
+                let remaining = self.config.max_body_size - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+                if body.len() == self.config.max_body_size {
+                    break;
+                }
+            }
+
+            return Some((
+                url,
+                content_type,
+                String::from_utf8_lossy(&body).into_owned(),
+            ));
+        }
+
+        None
+    }
+
+    async fn fetch_embed(&self, url: &str) -> Option<Embed> {
+        let url = Url::parse(url).ok()?;
+        let (page_url, content_type, html) =
+            tokio::time::timeout(self.config.timeout, self.fetch(url.clone(), "text/html"))
+                .await
+                .ok()??;
+        if !content_type.contains("html") {
+            return None;
+        }
+
+        let page = parse_metadata(&html, &page_url);
+        let mut oembed = OEmbed::default();
+        if let Some(oembed_url) = page
+            .oembed_url
+            .as_ref()
+            .and_then(|url| Url::parse(url).ok())
+        {
+            if let Ok(Some((_, _, json))) = tokio::time::timeout(
+                self.config.timeout,
+                self.fetch(oembed_url, "application/json"),
+            )
+            .await
+            {
+                oembed = serde_json::from_str(&json).unwrap_or_default();
+            }
+        }
+
+        build_embed(&url, page, oembed)
+    }
+
+    /// The preview of `url`, from the cache while it's fresh.
+    pub async fn unfurl(&self, url: &str) -> Option<Embed> {
+        let cached = sqlx::query_scalar!(
+            "SELECT embed FROM unfurl_cache WHERE url = $1 AND fetched_at > now() - make_interval(secs => $2);",
+            url,
+            CACHE_TTL.as_secs_f64()
+        )
+        .fetch_optional(&self.db)
+        .await;
+        if let Ok(Some(embed)) = cached {
+            return embed.and_then(|embed| serde_json::from_value(embed).ok());
+        }
+
+        let embed = self.fetch_embed(url).await;
+        let stored = embed
+            .as_ref()
+            .and_then(|embed| serde_json::to_value(embed).ok());
+        let _ = sqlx::query!(
+            "INSERT INTO unfurl_cache (url, embed) VALUES ($1, $2) ON CONFLICT (url) DO UPDATE SET embed = $2, fetched_at = now();",
+            url,
+            stored
+        )
+        .execute(&self.db)
+        .await;
+
+        embed
+    }
+
+    /// Attaches previews of the links in a message, returning the message if it got any.
+    ///
+    /// Messages which already have embeds are left alone.
+    pub async fn embed_message(&self, message_id: &str) -> Result<Option<Message>, sqlx::Error> {
+        let Some(message) =
+            sqlx::query_as!(Message, "SELECT * FROM messages WHERE id = $1;", message_id)
+                .fetch_optional(&self.db)
+                .await?
+        else {
+            return Ok(None);
+        };
+        if message.embeds.is_some() {
+            return Ok(None);
+        }
+
+        let mut embeds = Vec::new();
+        for url in extract_urls(&message.content) {
+            if let Some(embed) = self.unfurl(&url).await {
+                embeds.push(embed);
+            }
+        }
+        // previews are dropped from the end until they fit in a message together
+        while verify_embeds(&embeds).is_err() {
+            embeds.pop();
+        }
+        if embeds.is_empty() {
+            return Ok(None);
+        }
+
+        sqlx::query_as!(
+            Message,
+            "UPDATE messages SET embeds = $2 WHERE id = $1 AND embeds IS NULL RETURNING *;",
+            &message.id,
+            stored_embeds(&embeds)
+        )
+        .fetch_optional(&self.db)
+        .await
+    }
+}
+
+/// Unfurls the links of queued messages in the background, calling `on_embedded` with every
+/// message which got previews.
+pub async fn run_unfurler<F, Fut>(
+    unfurler: Unfurler,
+    mut jobs: UnboundedReceiver<UnfurlJob>,
+    on_embedded: F,
+) where
+    F: Fn(UnfurlJob, Message) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let unfurler = Arc::new(unfurler);
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_UNFURLS));
+
+    while let Some(job) = jobs.recv().await {
+        let Ok(permit) = permits.clone().acquire_owned().await else {
+            break;
+        };
+        let unfurler = unfurler.clone();
+        let on_embedded = on_embedded.clone();
+
+        tokio::spawn(async move {
+            if let Ok(Some(message)) = unfurler.embed_message(&job.message_id).await {
+                on_embedded(job, message).await;
+            }
+            drop(permit);
+        });
+    }
+}
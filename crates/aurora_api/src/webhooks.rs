@@ -0,0 +1,368 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use aurora_db::{
+    channel::{Channel, ChannelType},
+    embed::Embed,
+    guild::Guild,
+    message::{FullMessage, Message},
+    webhook::Webhook,
+    FromId,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, patch, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    channels::{double_option, get_channel},
+    embeds::{stored_embeds, verify_embeds},
+    error::{ErrorMessage, OVTError},
+    flags::GuildPermissions,
+    guilds::verify_permissions,
+    messages::{advance_last_message_id, hydrate_messages, queue_unfurl},
+    net::parse_web_url,
+    pubsub::{publish_guild, Event},
+    state::OVTState,
+    token::get_user,
+};
+
+/// How many messages a single webhook may post per [`WEBHOOK_RATE_LIMIT_WINDOW`].
+pub const WEBHOOK_RATE_LIMIT: u32 = 5;
+pub const WEBHOOK_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(2);
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Webhooks can only post where members could.
+fn verify_webhook_channel(channel: &Channel) -> Result<(), (StatusCode, Json<ErrorMessage>)> {
+    if matches!(
+        ChannelType::try_from(channel.r#type),
+        Ok(ChannelType::Text | ChannelType::Announcement)
+    ) {
+        Ok(())
+    } else {
+        Err(OVTError::InvalidChannelType.to_resp())
+    }
+}
+
+async fn get_webhook(
+    db: &sqlx::PgPool,
+    webhook_id: String,
+    guild_id: &str,
+) -> Result<Webhook, (StatusCode, Json<ErrorMessage>)> {
+    let webhook = Webhook::from_id(db, webhook_id)
+        .await
+        .map_err(|_| OVTError::WebhookNotFound.to_resp())?;
+
+    if webhook.guild_id != guild_id {
+        return Err(OVTError::WebhookNotFound.to_resp());
+    }
+
+    Ok(webhook)
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateWebhook {
+    #[validate(min_length = 1)]
+    #[validate(max_length = 80)]
+    name: String,
+    #[serde(default)]
+    avatar_url: Option<String>,
+}
+
+/// A freshly created webhook, the only time its token is ever shown.
+#[derive(Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    token: String,
+}
+
+pub async fn create_webhook(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<CreateWebhook>,
+) -> Result<Json<CreatedWebhook>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    if let Some(avatar_url) = &model.avatar_url {
+        parse_web_url(avatar_url).ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    verify_webhook_channel(&channel)?;
+
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let token = hex::encode(secret);
+
+    let webhook = sqlx::query_as!(
+        Webhook,
+        "INSERT INTO webhooks (id, guild_id, channel_id, creator_id, name, avatar_url, token_hash) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &guild.id,
+        &channel.id,
+        &actor.id,
+        model.name.trim(),
+        model.avatar_url,
+        hash_token(&token)
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::WebhookCreate(webhook.clone())).await?;
+
+    Ok(Json(CreatedWebhook { webhook, token }))
+}
+
+pub async fn get_channel_webhooks(
+    headers: HeaderMap,
+    Path((guild_id, channel_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Webhook>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    let channel = get_channel(&state.pg, &channel_id, &guild.id).await?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+
+    let webhooks = Webhook::from_channel(&state.pg, &channel.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(webhooks))
+}
+
+pub async fn get_guild_webhooks(
+    headers: HeaderMap,
+    Path(guild_id): Path<String>,
+    State(state): State<OVTState>,
+) -> Result<Json<Vec<Webhook>>, (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+
+    let webhooks = Webhook::from_guild(&state.pg, &guild.id)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    Ok(Json(webhooks))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ModifyWebhook {
+    #[serde(default)]
+    #[validate(min_length = 1)]
+    #[validate(max_length = 80)]
+    name: Option<String>,
+    /// `null` removes the avatar, leaving it out keeps the current one.
+    #[serde(default, deserialize_with = "double_option")]
+    avatar_url: Option<Option<String>>,
+    /// Moves the webhook to another channel in the same guild.
+    #[serde(default)]
+    channel_id: Option<String>,
+}
+
+pub async fn modify_webhook(
+    headers: HeaderMap,
+    Path((guild_id, webhook_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<ModifyWebhook>,
+) -> Result<Json<Webhook>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    if let Some(Some(avatar_url)) = &model.avatar_url {
+        parse_web_url(avatar_url).ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    let webhook = get_webhook(&state.pg, webhook_id, &guild.id).await?;
+
+    let channel_id = if let Some(channel_id) = &model.channel_id {
+        let channel = get_channel(&state.pg, channel_id, &guild.id).await?;
+        verify_webhook_channel(&channel)?;
+        channel.id
+    } else {
+        webhook.channel_id
+    };
+    let avatar_url = match model.avatar_url {
+        Some(avatar_url) => avatar_url,
+        None => webhook.avatar_url,
+    };
+
+    let modified_webhook = sqlx::query_as!(
+        Webhook,
+        "UPDATE webhooks SET name = COALESCE($1, name), avatar_url = $2, channel_id = $3 WHERE id = $4 RETURNING *;",
+        model.name.as_deref().map(str::trim),
+        avatar_url,
+        channel_id,
+        &webhook.id
+    )
+    .fetch_one(&state.pg)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::WebhookUpdate(modified_webhook.clone())).await?;
+
+    Ok(Json(modified_webhook))
+}
+
+pub async fn delete_webhook(
+    headers: HeaderMap,
+    Path((guild_id, webhook_id)): Path<(String, String)>,
+    State(state): State<OVTState>,
+) -> Result<(StatusCode, String), (StatusCode, Json<ErrorMessage>)> {
+    let (actor, _) = get_user(&headers, &state.key, &state.pg).await?;
+    let guild = Guild::from_id(&state.pg, guild_id)
+        .await
+        .map_err(|_| OVTError::GuildNotFound.to_resp())?;
+    verify_permissions(&state.pg, &actor, &guild, GuildPermissions::MANAGE_WEBHOOKS).await?;
+    let webhook = get_webhook(&state.pg, webhook_id, &guild.id).await?;
+
+    sqlx::query!("DELETE FROM webhooks WHERE id = $1;", &webhook.id)
+        .execute(&state.pg)
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&guild.id, Event::WebhookDelete(webhook)).await?;
+
+    Ok((StatusCode::NO_CONTENT, "".to_string()))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ExecuteWebhook {
+    /// Can only be left out when sending embeds.
+    #[serde(default)]
+    #[validate(max_length = 2048)]
+    content: String,
+    /// Overrides the webhook's name for this message only.
+    #[serde(default)]
+    #[validate(min_length = 1)]
+    #[validate(max_length = 80)]
+    username: Option<String>,
+    /// Overrides the webhook's avatar for this message only.
+    #[serde(default)]
+    avatar_url: Option<String>,
+    #[serde(default)]
+    embeds: Vec<Embed>,
+}
+
+/// Posts a message as the webhook, authenticated by the token in the url rather than a user.
+///
+/// Webhook messages don't parse mentions, so they never ping anyone.
+pub async fn execute_webhook(
+    Path((webhook_id, token)): Path<(String, String)>,
+    State(state): State<OVTState>,
+    Json(model): Json<ExecuteWebhook>,
+) -> Result<Json<FullMessage>, (StatusCode, Json<ErrorMessage>)> {
+    model
+        .validate()
+        .map_err(|_| OVTError::InvalidBody.to_resp())?;
+    if let Some(avatar_url) = &model.avatar_url {
+        parse_web_url(avatar_url).ok_or(OVTError::InvalidBody.to_resp())?;
+    }
+    if model.content.is_empty() && model.embeds.is_empty() {
+        return Err(OVTError::InvalidBody.to_resp());
+    }
+    verify_embeds(&model.embeds).map_err(|_| OVTError::InvalidEmbeds.to_resp())?;
+
+    // a wrong token looks exactly like a missing webhook
+    let webhook = Webhook::from_id(&state.pg, webhook_id)
+        .await
+        .map_err(|_| OVTError::WebhookNotFound.to_resp())?;
+    if webhook.token_hash != hash_token(&token) {
+        return Err(OVTError::WebhookNotFound.to_resp());
+    }
+
+    state
+        .webhooks
+        .check(webhook.id.clone())
+        .map_err(|_| OVTError::RateLimited.to_resp())?;
+
+    let mut tx = state
+        .pg
+        .begin()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    let message = sqlx::query_as!(
+        Message,
+        "INSERT INTO messages (id, channel_id, content, webhook_id, webhook_name, webhook_avatar_url, embeds) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;",
+        uuid7::uuid7().to_string(),
+        &webhook.channel_id,
+        model.content,
+        &webhook.id,
+        model.username.as_deref().map(str::trim).unwrap_or(&webhook.name),
+        model.avatar_url.as_ref().or(webhook.avatar_url.as_ref()),
+        stored_embeds(&model.embeds)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| OVTError::InternalServerError.to_resp())?;
+    advance_last_message_id(&mut tx, &webhook.channel_id, &message.id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|_| OVTError::InternalServerError.to_resp())?;
+
+    publish_guild(&webhook.guild_id, Event::MessageCreate(message.clone())).await?;
+
+    if model.embeds.is_empty() {
+        queue_unfurl(&webhook.guild_id, &message);
+    }
+
+    let mut messages = hydrate_messages(&state, vec![message]).await?;
+    Ok(Json(messages.remove(0)))
+}
+
+pub fn router() -> Router<OVTState> {
+    Router::<OVTState>::new()
+        .route(
+            "/guilds/:guild_id/channels/:channel_id/webhooks",
+            post(create_webhook).get(get_channel_webhooks),
+        )
+        .route("/guilds/:guild_id/webhooks", get(get_guild_webhooks))
+        .route(
+            "/guilds/:guild_id/webhooks/:webhook_id",
+            patch(modify_webhook).delete(delete_webhook),
+        )
+        .route("/webhooks/:webhook_id/:token", post(execute_webhook))
+}
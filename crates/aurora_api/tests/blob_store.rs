@@ -0,0 +1,227 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use aurora_api::storage::{
+    download_key, sign_download, verify_download, BlobError, BlobStore, LocalBlobStore, S3BlobStore,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode},
+    routing::any,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpListener;
+
+type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Just enough of an S3 server to check requests are signed and carry their payload hash.
+async fn object(
+    method: Method,
+    Path(path): Path<String>,
+    State(objects): State<Objects>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Vec<u8>) {
+    let authorization = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let payload_hash = headers
+        .get("x-amz-content-sha256")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if !authorization.starts_with("AWS4-HMAC-SHA256 Credential=minio/")
+        || !authorization.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date")
+        || payload_hash != hex::encode(Sha256::digest(&body))
+        || !headers.contains_key("x-amz-date")
+    {
+        return (StatusCode::FORBIDDEN, Vec::new());
+    }
+
+    let mut objects = objects.lock().unwrap();
+    match method {
+        Method::PUT => {
+            objects.insert(path, body.to_vec());
+            (StatusCode::OK, Vec::new())
+        }
+        Method::GET => match objects.get(&path) {
+            Some(data) => (StatusCode::OK, data.clone()),
+            None => (StatusCode::NOT_FOUND, Vec::new()),
+        },
+        Method::DELETE => {
+            objects.remove(&path);
+            (StatusCode::NO_CONTENT, Vec::new())
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, Vec::new()),
+    }
+}
+
+async fn spawn_s3() -> (String, Objects) {
+    let objects = Objects::default();
+    let app = Router::new()
+        .route("/*path", any(object))
+        .with_state(objects.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{address}"), objects)
+}
+
+async fn round_trip(store: &dyn BlobStore) {
+    let key = "attachments/channel/attachment/cat.png";
+
+    store.put(key, b"meow".to_vec(), "image/png").await.unwrap();
+    assert_eq!(store.get(key).await.unwrap(), b"meow");
+
+    store.delete(key).await.unwrap();
+    assert!(matches!(store.get(key).await, Err(BlobError::NotFound)));
+    // deleting twice is fine
+    store.delete(key).await.unwrap();
+}
+
+#[tokio::test]
+async fn local_store_round_trips() {
+    let root = std::env::temp_dir().join(uuid7::uuid7().to_string());
+    let store = LocalBlobStore::new(&root);
+
+    round_trip(&store).await;
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn local_store_rejects_escaping_keys() {
+    let store = LocalBlobStore::new(std::env::temp_dir().join(uuid7::uuid7().to_string()));
+
+    for key in [
+        "../secret",
+        "attachments/../../secret",
+        "",
+        "attachments//x",
+    ] {
+        assert!(matches!(
+            store.put(key, Vec::new(), "text/plain").await,
+            Err(BlobError::InvalidKey)
+        ));
+    }
+}
+
+#[tokio::test]
+async fn s3_store_round_trips() {
+    let (endpoint, objects) = spawn_s3().await;
+    let store = S3BlobStore::new(
+        &endpoint,
+        "aurora".to_string(),
+        "us-east-1".to_string(),
+        "minio".to_string(),
+        "minio-secret".to_string(),
+    );
+
+    store
+        .put("attachments/a/b/c.txt", b"hello".to_vec(), "text/plain")
+        .await
+        .unwrap();
+    assert_eq!(
+        objects.lock().unwrap().get("aurora/attachments/a/b/c.txt"),
+        Some(&b"hello".to_vec())
+    );
+
+    round_trip(&store).await;
+}
+
+#[tokio::test]
+async fn s3_store_surfaces_rejected_requests() {
+    let (endpoint, _) = spawn_s3().await;
+    let store = S3BlobStore::new(
+        &endpoint,
+        "aurora".to_string(),
+        "us-east-1".to_string(),
+        "someone-else".to_string(),
+        "secret".to_string(),
+    );
+
+    assert!(matches!(
+        store.put("a/b", b"hello".to_vec(), "text/plain").await,
+        Err(BlobError::Backend(_))
+    ));
+}
+
+#[test]
+fn download_signatures_verify() {
+    let expires = chrono::Utc::now().timestamp() + 60;
+    let signature = sign_download(b"secret", "id/cat.png", expires);
+
+    assert!(verify_download(
+        b"secret",
+        "id/cat.png",
+        expires,
+        &signature
+    ));
+    assert!(!verify_download(
+        b"other",
+        "id/cat.png",
+        expires,
+        &signature
+    ));
+    assert!(!verify_download(
+        b"secret",
+        "id/dog.png",
+        expires,
+        &signature
+    ));
+    assert!(!verify_download(
+        b"secret",
+        "id/cat.png",
+        expires + 1,
+        &signature
+    ));
+    assert!(!verify_download(
+        b"secret",
+        "id/cat.png",
+        expires,
+        "not hex"
+    ));
+}
+
+#[test]
+fn download_keys_differ_from_the_token_secret() {
+    let key = download_key("secret");
+
+    assert_eq!(key, download_key("secret"));
+    assert_ne!(key, b"secret");
+    assert_ne!(key, download_key("other"));
+}
+
+#[test]
+fn expired_download_signatures_fail() {
+    let expires = chrono::Utc::now().timestamp() - 1;
+    let signature = sign_download(b"secret", "id/cat.png", expires);
+
+    assert!(!verify_download(
+        b"secret",
+        "id/cat.png",
+        expires,
+        &signature
+    ));
+}
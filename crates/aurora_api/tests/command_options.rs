@@ -0,0 +1,162 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_api::commands::{resolve_options, verify_command, CommandError, OptionValue};
+use aurora_db::application::{CommandOption, CommandValue};
+use serde_json::json;
+
+fn options(value: serde_json::Value) -> Vec<CommandOption> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn values(value: serde_json::Value) -> Vec<OptionValue> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn remind_options() -> Vec<CommandOption> {
+    options(json!([
+        {"name": "what", "description": "What to remind about", "type": "string", "required": true, "max_length": 100},
+        {"name": "minutes", "description": "When", "type": "number", "required": true, "min_value": 1, "max_value": 1440},
+        {"name": "unit", "description": "Unit", "type": "string", "choices": [
+            {"name": "Minutes", "value": "m"},
+            {"name": "Hours", "value": "h"}
+        ]},
+        {"name": "who", "description": "Who to remind", "type": "user"}
+    ]))
+}
+
+#[test]
+fn accepts_valid_commands() {
+    assert_eq!(
+        verify_command("remind-me", "Reminds you", &remind_options()),
+        Ok(())
+    );
+    assert_eq!(verify_command("ping", "Pong", &[]), Ok(()));
+}
+
+#[test]
+fn rejects_invalid_names() {
+    assert_eq!(
+        verify_command("Remind Me", "Reminds you", &[]),
+        Err(CommandError::InvalidName("Remind Me".to_string()))
+    );
+    assert_eq!(
+        verify_command(&"a".repeat(33), "Reminds you", &[]),
+        Err(CommandError::InvalidName("a".repeat(33)))
+    );
+    assert_eq!(
+        verify_command("ping", "", &[]),
+        Err(CommandError::InvalidDescription("ping".to_string()))
+    );
+}
+
+#[test]
+fn rejects_inconsistent_options() {
+    let duplicate = options(json!([
+        {"name": "a", "description": "A", "type": "string"},
+        {"name": "a", "description": "A", "type": "integer"}
+    ]));
+    assert_eq!(
+        verify_command("ping", "Pong", &duplicate),
+        Err(CommandError::DuplicateOption("a".to_string()))
+    );
+
+    let required_last = options(json!([
+        {"name": "a", "description": "A", "type": "string"},
+        {"name": "b", "description": "B", "type": "string", "required": true}
+    ]));
+    assert_eq!(
+        verify_command("ping", "Pong", &required_last),
+        Err(CommandError::RequiredAfterOptional("b".to_string()))
+    );
+
+    for option in [
+        json!({"name": "a", "description": "A", "type": "boolean", "choices": [{"name": "Yes", "value": true}]}),
+        json!({"name": "a", "description": "A", "type": "integer", "choices": [{"name": "Half", "value": 0.5}]}),
+        json!({"name": "a", "description": "A", "type": "string", "min_value": 1}),
+        json!({"name": "a", "description": "A", "type": "number", "min_value": 2, "max_value": 1}),
+        json!({"name": "a", "description": "A", "type": "integer", "max_length": 5}),
+        json!({"name": "a", "description": "A", "type": "string", "max_length": 4096}),
+    ] {
+        assert_eq!(
+            verify_command("ping", "Pong", &options(json!([option]))),
+            Err(CommandError::InvalidConstraint("a".to_string()))
+        );
+    }
+}
+
+#[test]
+fn resolves_values_in_schema_order() {
+    let resolved = resolve_options(
+        &remind_options(),
+        values(json!([
+            {"name": "unit", "value": "h"},
+            {"name": "minutes", "value": 5},
+            {"name": "what", "value": "stretch"}
+        ])),
+    )
+    .unwrap();
+
+    assert_eq!(
+        resolved,
+        values(json!([
+            {"name": "what", "value": "stretch"},
+            {"name": "minutes", "value": 5.0},
+            {"name": "unit", "value": "h"}
+        ]))
+    );
+    assert_eq!(resolved[1].value, CommandValue::Number(5.0));
+}
+
+#[test]
+fn rejects_invalid_values() {
+    let cases = [
+        (
+            json!([{"name": "what", "value": "x"}]),
+            CommandError::MissingOption("minutes".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": "x"}, {"name": "minutes", "value": 1}, {"name": "when", "value": 1}]),
+            CommandError::UnknownOption("when".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": "x"}, {"name": "what", "value": "y"}, {"name": "minutes", "value": 1}]),
+            CommandError::DuplicateOption("what".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": 1}, {"name": "minutes", "value": 1}]),
+            CommandError::InvalidValue("what".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": "x".repeat(101)}, {"name": "minutes", "value": 1}]),
+            CommandError::InvalidValue("what".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": "x"}, {"name": "minutes", "value": 0.5}]),
+            CommandError::InvalidValue("minutes".to_string()),
+        ),
+        (
+            json!([{"name": "what", "value": "x"}, {"name": "minutes", "value": 1}, {"name": "unit", "value": "d"}]),
+            CommandError::InvalidValue("unit".to_string()),
+        ),
+    ];
+
+    for (given, error) in cases {
+        assert_eq!(
+            resolve_options(&remind_options(), values(given)),
+            Err(error)
+        );
+    }
+}
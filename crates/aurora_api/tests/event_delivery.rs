@@ -0,0 +1,274 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::{
+    atomic::{AtomicU16, Ordering},
+    Arc, Mutex,
+};
+
+use aurora_api::dispatch::{
+    deliver_due, enqueue, DeliveryConfig, GuildEvent, MAX_CONSECUTIVE_FAILURES,
+    MAX_DELIVERY_ATTEMPTS,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+
+#[path = "../../aurora_db/tests/common/mod.rs"]
+mod common;
+
+use common::{seed_actor, seed_guild};
+
+const SECRET: &str = "subscription-secret";
+
+#[derive(Clone, Default)]
+struct Receiver {
+    status: Arc<AtomicU16>,
+    received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+}
+
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: String) -> StatusCode {
+    receiver.received.lock().unwrap().push((headers, body));
+    StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap()
+}
+
+/// A local endpoint which records every delivery and answers with a settable status.
+async fn spawn_receiver(status: u16) -> (String, Receiver) {
+    let receiver = Receiver::default();
+    receiver.status.store(status, Ordering::SeqCst);
+    let app = Router::new()
+        .route("/events", post(receive))
+        .with_state(receiver.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{address}/events"), receiver)
+}
+
+fn local_config() -> DeliveryConfig {
+    DeliveryConfig {
+        allow_private_networks: true,
+        ..Default::default()
+    }
+}
+
+async fn subscribe(db: &PgPool, guild_id: &str, url: &str, event_types: &[&str]) -> String {
+    let id = uuid7::uuid7().to_string();
+    sqlx::query(
+        "INSERT INTO event_subscriptions (id, guild_id, url, secret, event_types) VALUES ($1, $2, $3, $4, $5);",
+    )
+    .bind(&id)
+    .bind(guild_id)
+    .bind(url)
+    .bind(SECRET)
+    .bind(event_types)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
+
+fn message_create(guild_id: &str) -> GuildEvent {
+    GuildEvent {
+        guild_id: guild_id.to_string(),
+        event_type: "MessageCreate".to_string(),
+        payload: r#"{"t":"MessageCreate","d":{"content":"hi"}}"#.to_string(),
+    }
+}
+
+/// Makes every pending delivery due now instead of waiting out its backoff.
+async fn skip_backoff(db: &PgPool) {
+    sqlx::query("UPDATE event_deliveries SET next_attempt_at = now();")
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+async fn delivery(db: &PgPool, subscription_id: &str) -> (String, i32, Option<i32>) {
+    sqlx::query_as(
+        "SELECT status, attempts, last_status_code FROM event_deliveries WHERE subscription_id = $1;",
+    )
+    .bind(subscription_id)
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+async fn subscription(db: &PgPool, subscription_id: &str) -> (bool, i32) {
+    sqlx::query_as("SELECT enabled, consecutive_failures FROM event_subscriptions WHERE id = $1;")
+        .bind(subscription_id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn delivers_signed_events_to_matching_subscriptions(db: PgPool) {
+    let owner_id = seed_actor(&db, "owner").await;
+    let (guild_id, _) = seed_guild(&db, &owner_id).await;
+    let (other_guild_id, _) = seed_guild(&db, &owner_id).await;
+    let (url, receiver) = spawn_receiver(204).await;
+    let subscription_id = subscribe(&db, &guild_id, &url, &["MessageCreate"]).await;
+    subscribe(&db, &guild_id, &url, &["MessageDelete"]).await;
+    subscribe(&db, &other_guild_id, &url, &["MessageCreate"]).await;
+
+    let event = message_create(&guild_id);
+    assert_eq!(enqueue(&db, &event).await.unwrap(), 1);
+    assert_eq!(deliver_due(&db, &local_config()).await.unwrap(), 1);
+
+    let received = receiver.received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1);
+    let (headers, body) = &received[0];
+    assert_eq!(body, &event.payload);
+    assert_eq!(headers["x-aurora-event"], "MessageCreate");
+
+    let timestamp = headers["x-aurora-timestamp"].to_str().unwrap();
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    let signature = headers["x-aurora-signature"]
+        .to_str()
+        .unwrap()
+        .strip_prefix("sha256=")
+        .unwrap();
+    mac.verify_slice(&hex::decode(signature).unwrap()).unwrap();
+
+    assert_eq!(
+        delivery(&db, &subscription_id).await,
+        ("succeeded".to_string(), 1, Some(204))
+    );
+    // nothing left to deliver
+    assert_eq!(deliver_due(&db, &local_config()).await.unwrap(), 0);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn retries_with_backoff_until_giving_up(db: PgPool) {
+    let owner_id = seed_actor(&db, "owner").await;
+    let (guild_id, _) = seed_guild(&db, &owner_id).await;
+    let (url, receiver) = spawn_receiver(500).await;
+    let subscription_id = subscribe(&db, &guild_id, &url, &["MessageCreate"]).await;
+    enqueue(&db, &message_create(&guild_id)).await.unwrap();
+
+    deliver_due(&db, &local_config()).await.unwrap();
+    assert_eq!(
+        delivery(&db, &subscription_id).await,
+        ("pending".to_string(), 1, Some(500))
+    );
+    // the retry isn't due yet
+    assert_eq!(deliver_due(&db, &local_config()).await.unwrap(), 0);
+
+    for _ in 1..MAX_DELIVERY_ATTEMPTS {
+        skip_backoff(&db).await;
+        deliver_due(&db, &local_config()).await.unwrap();
+    }
+
+    assert_eq!(
+        delivery(&db, &subscription_id).await,
+        ("failed".to_string(), MAX_DELIVERY_ATTEMPTS, Some(500))
+    );
+    assert_eq!(
+        receiver.received.lock().unwrap().len(),
+        MAX_DELIVERY_ATTEMPTS as usize
+    );
+    skip_backoff(&db).await;
+    assert_eq!(deliver_due(&db, &local_config()).await.unwrap(), 0);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn successes_reset_the_failure_count(db: PgPool) {
+    let owner_id = seed_actor(&db, "owner").await;
+    let (guild_id, _) = seed_guild(&db, &owner_id).await;
+    let (url, receiver) = spawn_receiver(503).await;
+    let subscription_id = subscribe(&db, &guild_id, &url, &["MessageCreate"]).await;
+    enqueue(&db, &message_create(&guild_id)).await.unwrap();
+
+    deliver_due(&db, &local_config()).await.unwrap();
+    assert_eq!(subscription(&db, &subscription_id).await, (true, 1));
+
+    receiver.status.store(200, Ordering::SeqCst);
+    skip_backoff(&db).await;
+    deliver_due(&db, &local_config()).await.unwrap();
+
+    assert_eq!(subscription(&db, &subscription_id).await, (true, 0));
+    assert_eq!(delivery(&db, &subscription_id).await.0, "succeeded");
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn disables_subscriptions_after_repeated_failures(db: PgPool) {
+    let owner_id = seed_actor(&db, "owner").await;
+    let (guild_id, _) = seed_guild(&db, &owner_id).await;
+    // nothing listens here once the listener is dropped
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+    drop(listener);
+    let subscription_id = subscribe(&db, &guild_id, &url, &["MessageCreate"]).await;
+    sqlx::query("UPDATE event_subscriptions SET consecutive_failures = $1;")
+        .bind(MAX_CONSECUTIVE_FAILURES - 1)
+        .execute(&db)
+        .await
+        .unwrap();
+    enqueue(&db, &message_create(&guild_id)).await.unwrap();
+    enqueue(&db, &message_create(&guild_id)).await.unwrap();
+
+    // both are attempted in the same batch, the first failure disables the subscription
+    deliver_due(&db, &local_config()).await.unwrap();
+
+    let (enabled, _) = subscription(&db, &subscription_id).await;
+    assert!(!enabled);
+    let pending: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM event_deliveries WHERE status = 'pending';")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(pending, 0);
+
+    // disabled subscriptions don't get new events
+    assert_eq!(enqueue(&db, &message_create(&guild_id)).await.unwrap(), 0);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn refuses_to_deliver_to_private_networks(db: PgPool) {
+    let owner_id = seed_actor(&db, "owner").await;
+    let (guild_id, _) = seed_guild(&db, &owner_id).await;
+    let (url, receiver) = spawn_receiver(204).await;
+    let subscription_id = subscribe(&db, &guild_id, &url, &["MessageCreate"]).await;
+    enqueue(&db, &message_create(&guild_id)).await.unwrap();
+
+    deliver_due(&db, &DeliveryConfig::default()).await.unwrap();
+
+    assert!(receiver.received.lock().unwrap().is_empty());
+    assert_eq!(
+        delivery(&db, &subscription_id).await,
+        ("pending".to_string(), 1, None)
+    );
+    let error: Option<String> =
+        sqlx::query_scalar("SELECT last_error FROM event_deliveries WHERE subscription_id = $1;")
+            .bind(&subscription_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(
+        error.as_deref(),
+        Some("url doesn't resolve to a public address")
+    );
+}
@@ -0,0 +1,167 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::Cursor;
+
+use aurora_api::images::{process_image, ImageError, ImageKind};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgb, RgbImage};
+
+const SECRET: &[u8] = b"SECRET-GPS-COORDINATES";
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), format).unwrap();
+    data
+}
+
+/// A wide JPEG, red on the left and blue on the right, carrying an EXIF block which
+/// says it should be rotated 90 degrees clockwise.
+fn rotated_jpeg() -> Vec<u8> {
+    let image = RgbImage::from_fn(200, 100, |x, _| {
+        if x < 100 {
+            Rgb([255, 0, 0])
+        } else {
+            Rgb([0, 0, 255])
+        }
+    });
+    let jpeg = encode(&DynamicImage::ImageRgb8(image), ImageFormat::Jpeg);
+
+    let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+    // one IFD entry: orientation (0x0112), SHORT, count 1, value 6
+    exif.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]);
+    exif.extend_from_slice(SECRET);
+
+    let mut data = jpeg[..2].to_vec();
+    data.extend_from_slice(&[0xff, 0xe1]);
+    data.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+    data.extend_from_slice(&exif);
+    data.extend_from_slice(&jpeg[2..]);
+    data
+}
+
+fn is_red(pixel: image::Rgba<u8>) -> bool {
+    pixel[0] > 200 && pixel[2] < 60
+}
+
+fn is_blue(pixel: image::Rgba<u8>) -> bool {
+    pixel[2] > 200 && pixel[0] < 60
+}
+
+#[test]
+fn avatars_are_resized_to_every_size() {
+    let processed = process_image(&rotated_jpeg(), ImageKind::Avatar).unwrap();
+
+    let widths: Vec<u32> = processed.variants.iter().map(|(width, _)| *width).collect();
+    assert_eq!(widths, [512, 256, 128, 64]);
+
+    for (width, data) in &processed.variants {
+        let decoded = image::load_from_memory_with_format(data, ImageFormat::Png).unwrap();
+        assert_eq!(decoded.dimensions(), (*width, *width));
+    }
+}
+
+#[test]
+fn banners_are_cropped_to_fill() {
+    let processed = process_image(&rotated_jpeg(), ImageKind::Banner).unwrap();
+
+    for ((width, data), (expected_width, expected_height)) in
+        processed.variants.iter().zip(ImageKind::Banner.sizes())
+    {
+        assert_eq!(width, expected_width);
+        let decoded = image::load_from_memory_with_format(data, ImageFormat::Png).unwrap();
+        assert_eq!(decoded.dimensions(), (*expected_width, *expected_height));
+    }
+}
+
+#[test]
+fn exif_is_stripped() {
+    let processed = process_image(&rotated_jpeg(), ImageKind::Avatar).unwrap();
+
+    for (_, data) in &processed.variants {
+        assert!(!data.windows(SECRET.len()).any(|window| window == SECRET));
+        assert!(!data
+            .windows(4)
+            .any(|window| window == b"Exif" || window == b"eXIf"));
+    }
+}
+
+#[test]
+fn exif_orientation_is_applied_before_stripping() {
+    let processed = process_image(&rotated_jpeg(), ImageKind::Avatar).unwrap();
+    let decoded =
+        image::load_from_memory_with_format(&processed.variants[0].1, ImageFormat::Png).unwrap();
+
+    // rotated clockwise, the red half ends up on top
+    assert!(is_red(decoded.get_pixel(400, 20)));
+    assert!(is_blue(decoded.get_pixel(100, 490)));
+}
+
+#[test]
+fn identical_uploads_share_a_hash() {
+    let first = process_image(&rotated_jpeg(), ImageKind::Avatar).unwrap();
+    let second = process_image(&rotated_jpeg(), ImageKind::Avatar).unwrap();
+    let other = process_image(
+        &encode(
+            &DynamicImage::ImageRgb8(RgbImage::new(64, 64)),
+            ImageFormat::Png,
+        ),
+        ImageKind::Avatar,
+    )
+    .unwrap();
+
+    assert_eq!(first.hash, second.hash);
+    assert_ne!(first.hash, other.hash);
+    assert_eq!(first.hash.len(), 64);
+}
+
+#[test]
+fn non_images_are_rejected() {
+    for data in [
+        b"definitely not an image".as_slice(),
+        b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>".as_slice(),
+        b"".as_slice(),
+    ] {
+        assert!(matches!(
+            process_image(data, ImageKind::Avatar),
+            Err(ImageError::Unsupported)
+        ));
+    }
+}
+
+#[test]
+fn truncated_images_are_rejected() {
+    let png = encode(
+        &DynamicImage::ImageRgb8(RgbImage::new(64, 64)),
+        ImageFormat::Png,
+    );
+
+    assert!(matches!(
+        process_image(&png[..png.len() / 2], ImageKind::Avatar),
+        Err(ImageError::Invalid)
+    ));
+}
+
+#[test]
+fn oversized_images_are_rejected() {
+    let png = encode(
+        &DynamicImage::ImageRgb8(RgbImage::new(9000, 1)),
+        ImageFormat::Png,
+    );
+
+    assert!(matches!(
+        process_image(&png, ImageKind::Avatar),
+        Err(ImageError::TooLarge)
+    ));
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_api::components::{verify_component_use, verify_components, ComponentError};
+use aurora_db::component::Component;
+use serde_json::json;
+
+fn components(value: serde_json::Value) -> Vec<Component> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn poll_components() -> Vec<Component> {
+    components(json!([
+        {"type": "action_row", "components": [
+            {"type": "button", "style": "primary", "label": "Vote", "custom_id": "vote"},
+            {"type": "button", "style": "danger", "label": "Close", "custom_id": "close", "disabled": true},
+            {"type": "button", "style": "link", "label": "Docs", "url": "https://example.com/docs"}
+        ]},
+        {"type": "action_row", "components": [
+            {"type": "string_select", "custom_id": "colour", "min_values": 1, "max_values": 2, "options": [
+                {"label": "Red", "value": "red"},
+                {"label": "Green", "value": "green"},
+                {"label": "Blue", "value": "blue"}
+            ]}
+        ]}
+    ]))
+}
+
+fn verify(value: serde_json::Value) -> Result<(), ComponentError> {
+    verify_components(&components(value))
+}
+
+#[test]
+fn accepts_valid_components() {
+    assert_eq!(verify_components(&poll_components()), Ok(()));
+    assert_eq!(verify_components(&[]), Ok(()));
+}
+
+#[test]
+fn rejects_invalid_layouts() {
+    let button = json!({"type": "button", "style": "primary", "label": "A", "custom_id": "a"});
+    let row = json!({"type": "action_row", "components": [button]});
+
+    assert_eq!(verify(json!([button])), Err(ComponentError::InvalidLayout));
+    assert_eq!(
+        verify(json!([{"type": "action_row", "components": [row]}])),
+        Err(ComponentError::InvalidLayout)
+    );
+    assert_eq!(
+        verify(json!([{"type": "action_row", "components": []}])),
+        Err(ComponentError::InvalidRow)
+    );
+    assert_eq!(
+        verify(json!([row, row, row, row, row, row])),
+        Err(ComponentError::TooManyRows)
+    );
+
+    let buttons: Vec<_> = (0..6)
+        .map(|i| json!({"type": "button", "style": "primary", "label": "A", "custom_id": i.to_string()}))
+        .collect();
+    assert_eq!(
+        verify(json!([{"type": "action_row", "components": buttons}])),
+        Err(ComponentError::InvalidRow)
+    );
+
+    let select = json!({"type": "string_select", "custom_id": "s", "options": [{"label": "A", "value": "a"}]});
+    assert_eq!(
+        verify(
+            json!([{"type": "action_row", "components": [select, {"type": "button", "style": "primary", "label": "B", "custom_id": "b"}]}])
+        ),
+        Err(ComponentError::InvalidRow)
+    );
+}
+
+#[test]
+fn rejects_invalid_buttons_and_menus() {
+    for button in [
+        json!({"type": "button", "style": "link", "label": "A", "custom_id": "a"}),
+        json!({"type": "button", "style": "link", "label": "A", "url": "javascript:alert(1)"}),
+        json!({"type": "button", "style": "primary", "label": "A"}),
+        json!({"type": "button", "style": "primary", "label": "A", "custom_id": "a", "url": "https://example.com"}),
+        json!({"type": "button", "style": "primary", "label": "", "custom_id": "a"}),
+    ] {
+        assert_eq!(
+            verify(json!([{"type": "action_row", "components": [button]}])),
+            Err(ComponentError::InvalidButton)
+        );
+    }
+
+    assert_eq!(
+        verify(json!([
+            {"type": "action_row", "components": [{"type": "button", "style": "primary", "label": "A", "custom_id": "a"}]},
+            {"type": "action_row", "components": [{"type": "button", "style": "primary", "label": "B", "custom_id": "a"}]}
+        ])),
+        Err(ComponentError::DuplicateCustomId("a".to_string()))
+    );
+
+    for menu in [
+        json!({"type": "string_select", "custom_id": "s", "options": []}),
+        json!({"type": "string_select", "custom_id": "s", "max_values": 2, "options": [{"label": "A", "value": "a"}]}),
+        json!({"type": "string_select", "custom_id": "s", "options": [{"label": "A", "value": "a"}, {"label": "B", "value": "a"}]}),
+        json!({"type": "string_select", "custom_id": "s", "min_values": 2, "max_values": 1, "options": [{"label": "A", "value": "a"}, {"label": "B", "value": "b"}]}),
+    ] {
+        assert_eq!(
+            verify(json!([{"type": "action_row", "components": [menu]}])),
+            Err(ComponentError::InvalidSelectMenu("s".to_string()))
+        );
+    }
+}
+
+#[test]
+fn checks_component_use() {
+    let components = poll_components();
+    let values = |values: &[&str]| {
+        values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(verify_component_use(&components, "vote", &[]), Ok(()));
+    assert_eq!(
+        verify_component_use(&components, "colour", &values(&["red", "blue"])),
+        Ok(())
+    );
+
+    assert_eq!(
+        verify_component_use(&components, "missing", &[]),
+        Err(ComponentError::UnknownComponent("missing".to_string()))
+    );
+    assert_eq!(
+        verify_component_use(&components, "close", &[]),
+        Err(ComponentError::Unusable("close".to_string()))
+    );
+    assert_eq!(
+        verify_component_use(&components, "vote", &values(&["red"])),
+        Err(ComponentError::InvalidValues("vote".to_string()))
+    );
+    for picked in [
+        &[][..],
+        &["red", "green", "blue"],
+        &["red", "red"],
+        &["purple"],
+    ] {
+        assert_eq!(
+            verify_component_use(&components, "colour", &values(picked)),
+            Err(ComponentError::InvalidValues("colour".to_string()))
+        );
+    }
+}
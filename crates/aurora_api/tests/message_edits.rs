@@ -0,0 +1,34 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_api::edits::ModifyMessage;
+use serde_json::json;
+use serde_valid::Validate;
+
+fn edit(content: &str) -> ModifyMessage {
+    serde_json::from_value(json!({ "content": content })).unwrap()
+}
+
+#[test]
+fn accepts_edits_within_the_content_limits() {
+    assert!(edit("fixed a typo").validate().is_ok());
+    assert!(edit(&"a".repeat(2048)).validate().is_ok());
+}
+
+#[test]
+fn rejects_empty_and_oversized_edits() {
+    assert!(edit("").validate().is_err());
+    assert!(edit(&"a".repeat(2049)).validate().is_err());
+}
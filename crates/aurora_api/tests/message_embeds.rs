@@ -0,0 +1,115 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_api::embeds::{verify_embeds, EmbedError, MAX_EMBEDS};
+use aurora_db::embed::Embed;
+use serde_json::json;
+
+fn embeds(value: serde_json::Value) -> Vec<Embed> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn verify(value: serde_json::Value) -> Result<(), EmbedError> {
+    verify_embeds(&embeds(value))
+}
+
+#[test]
+fn accepts_full_embeds() {
+    assert_eq!(
+        verify(json!([{
+            "title": "Release 1.2",
+            "description": "Faster and smaller.",
+            "url": "https://example.com/releases/1.2",
+            "color": 0x5865f2,
+            "fields": [
+                {"name": "Downloads", "value": "1,024", "inline": true},
+                {"name": "Size", "value": "4 MB", "inline": true}
+            ],
+            "footer": {"text": "Released today", "icon_url": "https://example.com/icon.png"},
+            "author": {"name": "Aurora", "url": "https://example.com"},
+            "image": {"url": "https://example.com/banner.png"},
+            "thumbnail": {"url": "https://example.com/thumb.png"}
+        }])),
+        Ok(())
+    );
+}
+
+#[test]
+fn rejects_empty_and_oversized_embeds() {
+    assert_eq!(verify(json!([{}])), Err(EmbedError::Empty));
+    assert_eq!(
+        verify(json!(vec![json!({"title": "a"}); MAX_EMBEDS + 1])),
+        Err(EmbedError::TooManyEmbeds)
+    );
+    assert_eq!(
+        verify(json!([{"fields": vec![json!({"name": "a", "value": "b"}); 26]}])),
+        Err(EmbedError::TooManyFields)
+    );
+    assert_eq!(
+        verify(json!([{"title": "a", "color": 0x1000000}])),
+        Err(EmbedError::InvalidColor)
+    );
+}
+
+#[test]
+fn enforces_field_limits() {
+    let cases = [
+        (json!({"title": "a".repeat(257)}), "title"),
+        (json!({"title": " "}), "title"),
+        (json!({"description": "a".repeat(4097)}), "description"),
+        (
+            json!({"fields": [{"name": "a", "value": "b".repeat(1025)}]}),
+            "fields.0.value",
+        ),
+        (
+            json!({"fields": [{"name": "a", "value": "b"}, {"name": "", "value": "b"}]}),
+            "fields.1.name",
+        ),
+        (json!({"footer": {"text": "a".repeat(2049)}}), "footer.text"),
+        (json!({"author": {"name": "a".repeat(257)}}), "author.name"),
+    ];
+
+    for (embed, path) in cases {
+        assert_eq!(
+            verify(json!([embed])),
+            Err(EmbedError::InvalidText(path.to_string()))
+        );
+    }
+}
+
+#[test]
+fn only_allows_web_urls() {
+    for (embed, path) in [
+        (json!({"title": "a", "url": "javascript:alert(1)"}), "url"),
+        (json!({"image": {"url": "file:///etc/passwd"}}), "image.url"),
+        (json!({"thumbnail": {"url": "not a url"}}), "thumbnail.url"),
+        (
+            json!({"author": {"name": "a", "icon_url": "ftp://example.com/a.png"}}),
+            "author.icon_url",
+        ),
+    ] {
+        assert_eq!(
+            verify(json!([embed])),
+            Err(EmbedError::InvalidUrl(path.to_string()))
+        );
+    }
+}
+
+#[test]
+fn limits_total_length_across_embeds() {
+    let embed = json!({"description": "a".repeat(4000)});
+    assert_eq!(verify(json!([embed])), Ok(()));
+    assert_eq!(verify(json!([embed, embed])), Err(EmbedError::TooLong));
+}
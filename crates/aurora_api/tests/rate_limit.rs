@@ -0,0 +1,92 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use aurora_api::ratelimit::RateLimiter;
+
+const WINDOW: Duration = Duration::from_secs(5);
+
+#[test]
+fn allows_once_per_window() {
+    let limiter = RateLimiter::new(1, WINDOW);
+    let start = Instant::now();
+
+    assert_eq!(limiter.check_at("a", start), Ok(()));
+    assert_eq!(
+        limiter.check_at("a", start + Duration::from_secs(2)),
+        Err(Duration::from_secs(3))
+    );
+    assert_eq!(limiter.check_at("a", start + WINDOW), Ok(()));
+}
+
+#[test]
+fn rejected_hits_do_not_extend_the_window() {
+    let limiter = RateLimiter::new(1, WINDOW);
+    let start = Instant::now();
+
+    limiter.check_at("a", start).unwrap();
+    limiter
+        .check_at("a", start + Duration::from_secs(4))
+        .unwrap_err();
+
+    assert_eq!(limiter.check_at("a", start + WINDOW), Ok(()));
+}
+
+#[test]
+fn allows_bursts_up_to_the_limit() {
+    let limiter = RateLimiter::new(3, WINDOW);
+    let start = Instant::now();
+
+    for i in 0..3 {
+        assert_eq!(
+            limiter.check_at("a", start + Duration::from_secs(i)),
+            Ok(())
+        );
+    }
+    assert_eq!(
+        limiter.check_at("a", start + Duration::from_secs(4)),
+        Err(Duration::from_secs(1))
+    );
+    assert_eq!(limiter.check_at("a", start + WINDOW), Ok(()));
+}
+
+#[test]
+fn keys_are_limited_separately() {
+    let limiter = RateLimiter::new(1, WINDOW);
+    let start = Instant::now();
+
+    assert_eq!(limiter.check_at(("user", "general"), start), Ok(()));
+    assert_eq!(limiter.check_at(("user", "random"), start), Ok(()));
+    assert_eq!(limiter.check_at(("other", "general"), start), Ok(()));
+    assert!(limiter.check_at(("user", "general"), start).is_err());
+}
+
+#[test]
+fn sweeping_expired_keys_keeps_live_ones() {
+    let limiter = RateLimiter::new(1, WINDOW);
+    let start = Instant::now();
+
+    for i in 0..5000 {
+        limiter.check_at(i, start).unwrap();
+    }
+    let later = start + Duration::from_secs(3);
+    limiter.check_at(-1, later).unwrap();
+
+    // the sweep on this hit drops the first batch, but not the one still inside its window
+    limiter.check_at(-2, start + WINDOW).unwrap();
+    assert!(limiter.check_at(-1, start + WINDOW).is_err());
+    assert_eq!(limiter.check_at(0, start + WINDOW), Ok(()));
+}
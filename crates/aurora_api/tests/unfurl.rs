@@ -0,0 +1,358 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use aurora_api::{
+    net::is_public_ip,
+    unfurl::{extract_urls, parse_metadata, PageMetadata, UnfurlConfig, Unfurler},
+};
+use aurora_db::embed::{Embed, EmbedAuthor, EmbedFooter, EmbedMedia};
+use axum::{
+    extract::State,
+    http::header::{CONTENT_TYPE, LOCATION},
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use reqwest::{StatusCode, Url};
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+
+const PAGE: &str = r##"<!doctype html>
+<html>
+<head>
+    <title>Ignored when og:title is set</title>
+    <meta property="og:title" content="Aurora &amp; friends">
+    <meta property="og:description" content="  A chat   app.  ">
+    <meta property="og:image" content="/banner.png">
+    <meta name="theme-color" content="#5865F2">
+    <link rel="alternate" type="application/json+oembed" href="/oembed?url=page">
+</head>
+<body></body>
+</html>"##;
+
+#[derive(Clone, Default)]
+struct Fixture {
+    hits: Arc<AtomicUsize>,
+}
+
+async fn page(State(fixture): State<Fixture>) -> Html<&'static str> {
+    fixture.hits.fetch_add(1, Ordering::SeqCst);
+    Html(PAGE)
+}
+
+async fn oembed() -> Json<serde_json::Value> {
+    Json(json!({
+        "version": "1.0",
+        "type": "rich",
+        "author_name": "V.J.",
+        "author_url": "https://example.com/vj",
+        "provider_name": "Example"
+    }))
+}
+
+async fn redirect() -> impl IntoResponse {
+    (StatusCode::FOUND, [(LOCATION, "/page")])
+}
+
+async fn redirect_loop() -> impl IntoResponse {
+    (StatusCode::FOUND, [(LOCATION, "/loop")])
+}
+
+async fn slow() -> Html<&'static str> {
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    Html(PAGE)
+}
+
+/// The page's tags only come after more padding than the unfurler reads.
+async fn big() -> Html<String> {
+    Html(format!("<html>{}{PAGE}", " ".repeat(256 * 1024)))
+}
+
+async fn plain_text() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/plain")], PAGE)
+}
+
+/// A local site to unfurl, counting how often its page is fetched.
+async fn spawn_fixture() -> (String, Fixture) {
+    let fixture = Fixture::default();
+    let app = Router::new()
+        .route("/page", get(page))
+        .route("/oembed", get(oembed))
+        .route("/redirect", get(redirect))
+        .route("/loop", get(redirect_loop))
+        .route("/slow", get(slow))
+        .route("/big", get(big))
+        .route("/text", get(plain_text))
+        .with_state(fixture.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{address}"), fixture)
+}
+
+fn local_config() -> UnfurlConfig {
+    UnfurlConfig {
+        timeout: Duration::from_millis(500),
+        max_body_size: 64 * 1024,
+        allow_private_networks: true,
+        ..Default::default()
+    }
+}
+
+fn expected_embed(base: &str, url: &str) -> Embed {
+    Embed {
+        title: Some("Aurora & friends".to_string()),
+        description: Some("A chat app.".to_string()),
+        url: Some(url.to_string()),
+        color: Some(0x5865f2),
+        footer: Some(EmbedFooter {
+            text: "Example".to_string(),
+            icon_url: None,
+        }),
+        author: Some(EmbedAuthor {
+            name: "V.J.".to_string(),
+            url: Some("https://example.com/vj".to_string()),
+            icon_url: None,
+        }),
+        thumbnail: Some(EmbedMedia {
+            url: format!("{base}/banner.png"),
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn extracts_links() {
+    assert_eq!(
+        extract_urls(
+            "see https://example.com/a, (https://example.com/b) and <https://example.com/c>! \
+             also https://en.wikipedia.org/wiki/Rust_(programming_language). https://example.com/a"
+        ),
+        vec![
+            "https://example.com/a",
+            "https://example.com/b",
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+        ]
+    );
+    assert_eq!(
+        extract_urls("ftp://example.com http:// nothing"),
+        Vec::<String>::new()
+    );
+
+    let many = (0..10)
+        .map(|i| format!("https://example.com/{i}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    assert_eq!(extract_urls(&many).len(), 5);
+}
+
+#[test]
+fn only_public_addresses_are_allowed() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "255.255.255.255",
+        "::1",
+        "::",
+        "::ffff:127.0.0.1",
+        "::ffff:10.0.0.1",
+        "fc00::1",
+        "fe80::1",
+        "64:ff9b::a00:1",
+        "2002:c0a8:101::1",
+    ] {
+        assert!(!is_public_ip(ip.parse::<IpAddr>().unwrap()), "{ip}");
+    }
+
+    for ip in [
+        "93.184.216.34",
+        "1.1.1.1",
+        "2606:4700:4700::1111",
+        "::ffff:1.1.1.1",
+    ] {
+        assert!(is_public_ip(ip.parse::<IpAddr>().unwrap()), "{ip}");
+    }
+}
+
+#[test]
+fn parses_page_metadata() {
+    let base = Url::parse("https://example.com/posts/1").unwrap();
+
+    assert_eq!(
+        parse_metadata(PAGE, &base),
+        PageMetadata {
+            title: Some("Aurora & friends".to_string()),
+            description: Some("A chat app.".to_string()),
+            site_name: None,
+            image: Some("https://example.com/banner.png".to_string()),
+            color: Some(0x5865f2),
+            oembed_url: Some("https://example.com/oembed?url=page".to_string()),
+        }
+    );
+
+    let plain = "<HTML><TITLE>\n  Just a &lt;title&gt; </TITLE><meta name='description' content=\"Plain&#39;s page\"></HTML>";
+    assert_eq!(
+        parse_metadata(plain, &base),
+        PageMetadata {
+            title: Some("Just a <title>".to_string()),
+            description: Some("Plain's page".to_string()),
+            ..Default::default()
+        }
+    );
+
+    let unsafe_image = r#"<meta property="og:title" content="a"><meta property="og:image" content="javascript:alert(1)">"#;
+    assert_eq!(parse_metadata(unsafe_image, &base).image, None);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn unfurls_pages_with_oembed(db: PgPool) {
+    let (base, _) = spawn_fixture().await;
+    let unfurler = Unfurler::new(db, local_config());
+
+    let url = format!("{base}/page");
+    assert_eq!(
+        unfurler.unfurl(&url).await,
+        Some(expected_embed(&base, &url))
+    );
+
+    // the preview links to what was posted, not where it redirected to
+    let redirected = format!("{base}/redirect");
+    assert_eq!(
+        unfurler.unfurl(&redirected).await,
+        Some(expected_embed(&base, &redirected))
+    );
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn caches_previews_by_url(db: PgPool) {
+    let (base, fixture) = spawn_fixture().await;
+    let unfurler = Unfurler::new(db.clone(), local_config());
+    let url = format!("{base}/page");
+
+    let first = unfurler.unfurl(&url).await;
+    let second = unfurler.unfurl(&url).await;
+    assert!(first.is_some());
+    assert_eq!(first, second);
+    assert_eq!(fixture.hits.load(Ordering::SeqCst), 1);
+
+    // stale entries are fetched again
+    sqlx::query("UPDATE unfurl_cache SET fetched_at = now() - INTERVAL '2 days';")
+        .execute(&db)
+        .await
+        .unwrap();
+    assert_eq!(unfurler.unfurl(&url).await, first);
+    assert_eq!(fixture.hits.load(Ordering::SeqCst), 2);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn refuses_private_addresses(db: PgPool) {
+    let (base, fixture) = spawn_fixture().await;
+    let unfurler = Unfurler::new(db, UnfurlConfig::default());
+
+    assert_eq!(unfurler.unfurl(&format!("{base}/page")).await, None);
+    assert_eq!(
+        unfurler
+            .unfurl(&base.replace("127.0.0.1", "localhost"))
+            .await,
+        None
+    );
+    assert_eq!(fixture.hits.load(Ordering::SeqCst), 0);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn gives_up_on_misbehaving_pages(db: PgPool) {
+    let (base, _) = spawn_fixture().await;
+    let unfurler = Unfurler::new(db, local_config());
+
+    let start = Instant::now();
+    assert_eq!(unfurler.unfurl(&format!("{base}/slow")).await, None);
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    assert_eq!(unfurler.unfurl(&format!("{base}/big")).await, None);
+    assert_eq!(unfurler.unfurl(&format!("{base}/loop")).await, None);
+    assert_eq!(unfurler.unfurl(&format!("{base}/text")).await, None);
+    assert_eq!(unfurler.unfurl(&format!("{base}/missing")).await, None);
+}
+
+async fn seed_message(db: &PgPool, content: &str) -> String {
+    let id = uuid7::uuid7().to_string();
+    sqlx::query("INSERT INTO actors (id, username) VALUES ($1, $1);")
+        .bind(&id)
+        .execute(db)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO accounts (id, actor_id) VALUES ($1, $1);")
+        .bind(&id)
+        .execute(db)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO guilds (id, owner_id, name) VALUES ($1, $1, 'guild');")
+        .bind(&id)
+        .execute(db)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO channels (id, guild_id, name, position) VALUES ($1, $1, 'general', 0);",
+    )
+    .bind(&id)
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO messages (id, author_id, channel_id, content) VALUES ($1, $1, $1, $2);",
+    )
+    .bind(&id)
+    .bind(content)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn attaches_previews_to_messages(db: PgPool) {
+    let (base, _) = spawn_fixture().await;
+    let unfurler = Unfurler::new(db.clone(), local_config());
+
+    let url = format!("{base}/page");
+    let message_id = seed_message(&db, &format!("look {url} and {base}/missing")).await;
+
+    let message = unfurler.embed_message(&message_id).await.unwrap().unwrap();
+    let embeds: Vec<Embed> = serde_json::from_value(message.embeds.unwrap()).unwrap();
+    assert_eq!(embeds, vec![expected_embed(&base, &url)]);
+
+    // messages are only unfurled once
+    assert!(unfurler.embed_message(&message_id).await.unwrap().is_none());
+
+    let plain = seed_message(&db, "no links here").await;
+    assert!(unfurler.embed_message(&plain).await.unwrap().is_none());
+}
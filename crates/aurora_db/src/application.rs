@@ -0,0 +1,208 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, types::Json};
+
+use crate::{DBError, FromId, FromIdResult};
+
+/// A bot, owned by a user, which can register commands.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Application {
+    pub id: String,
+    pub owner_id: String,
+    pub bot_id: String,
+    pub name: String,
+    pub interactions_url: Option<String>,
+    #[serde(skip)]
+    pub interactions_secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromId<String> for Application {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(Application, "SELECT * FROM applications WHERE id = $1;", id)
+            .fetch_one(db)
+            .await
+            .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl Application {
+    pub async fn from_owner(db: &sqlx::PgPool, owner_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Application,
+            "SELECT * FROM applications WHERE owner_id = $1 ORDER BY id;",
+            owner_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    /// The application a bot account belongs to.
+    pub async fn from_bot(db: &sqlx::PgPool, bot_id: &str) -> Result<Self, DBError> {
+        sqlx::query_as!(
+            Application,
+            "SELECT * FROM applications WHERE bot_id = $1;",
+            bot_id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandOptionType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// A user id, which has to belong to a member of the guild.
+    User,
+    /// A channel id, which has to belong to the guild.
+    Channel,
+}
+
+/// A value given to an option, users and channels are given as ids.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum CommandValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CommandChoice {
+    pub name: String,
+    pub value: CommandValue,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CommandOption {
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub r#type: CommandOptionType,
+    #[serde(default)]
+    pub required: bool,
+    /// When given, the value has to be one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<CommandChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ApplicationCommand {
+    pub id: String,
+    pub application_id: String,
+    pub guild_id: Option<String>,
+    pub name: String,
+    pub description: String,
+    pub options: Json<Vec<CommandOption>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromId<String> for ApplicationCommand {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(
+            ApplicationCommand,
+            r#"SELECT id, application_id, guild_id, name, description, options AS "options: Json<Vec<CommandOption>>", created_at FROM application_commands WHERE id = $1;"#,
+            id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl ApplicationCommand {
+    /// An application's global commands, or its commands in one guild.
+    pub async fn from_application(
+        db: &sqlx::PgPool,
+        application_id: &str,
+        guild_id: Option<&str>,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            ApplicationCommand,
+            r#"SELECT id, application_id, guild_id, name, description, options AS "options: Json<Vec<CommandOption>>", created_at
+            FROM application_commands WHERE application_id = $1 AND guild_id IS NOT DISTINCT FROM $2 ORDER BY name;"#,
+            application_id,
+            guild_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    /// Every command members of a guild can use, from the applications whose bots are in it.
+    pub async fn from_guild(db: &sqlx::PgPool, guild_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            ApplicationCommand,
+            r#"SELECT command.id, command.application_id, command.guild_id, command.name, command.description, command.options AS "options: Json<Vec<CommandOption>>", command.created_at
+            FROM application_commands command
+            JOIN applications application ON application.id = command.application_id
+            JOIN guild_members member ON member.user_id = application.bot_id AND member.guild_id = $1
+            WHERE command.guild_id IS NULL OR command.guild_id = $1
+            ORDER BY command.name, command.id;"#,
+            guild_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
+
+/// A single use of a command, waiting for its application to respond.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Interaction {
+    pub id: String,
+    pub application_id: String,
+    /// Set for commands, while component interactions have a message and custom id instead.
+    pub command_id: Option<String>,
+    pub message_id: Option<String>,
+    pub custom_id: Option<String>,
+    /// The options picked in a select menu.
+    #[serde(rename = "values")]
+    pub component_values: Vec<String>,
+    pub guild_id: String,
+    pub channel_id: String,
+    pub user_id: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub responded: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromId<String> for Interaction {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(Interaction, "SELECT * FROM interactions WHERE id = $1;", id)
+            .fetch_one(db)
+            .await
+            .map_err(|_| DBError::RowNotFound)
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{DBError, FromId, FromIdResult};
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: String,
+    pub message_id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i32,
+    #[serde(skip)]
+    pub blob_key: String,
+    /// A signed download url, filled in by the API since it expires.
+    #[sqlx(default)]
+    pub url: String,
+}
+
+impl FromId<String> for Attachment {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(
+            Attachment,
+            "SELECT *, '' AS \"url!\" FROM attachments WHERE id = $1;",
+            id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl Attachment {
+    pub async fn from_messages(
+        db: &sqlx::PgPool,
+        message_ids: &[String],
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Attachment,
+            "SELECT *, '' AS \"url!\" FROM attachments WHERE message_id = ANY($1) ORDER BY id;",
+            message_ids
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
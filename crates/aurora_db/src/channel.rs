@@ -32,6 +32,36 @@ pub struct Channel {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_message_id: Option<String>,
     pub position: i32,
+    #[serde(rename = "type")]
+    pub r#type: i32,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum ChannelType {
+    Text = 0,
+    Category = 1,
+    Announcement = 2,
+    Thread = 3,
+    Forum = 4,
+}
+
+impl TryFrom<i32> for ChannelType {
+    type Error = ();
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Text),
+            1 => Ok(Self::Category),
+            2 => Ok(Self::Announcement),
+            3 => Ok(Self::Thread),
+            4 => Ok(Self::Forum),
+            _ => Err(()),
+        }
+    }
 }
 
 impl FromId<String> for Channel {
@@ -42,3 +72,18 @@ impl FromId<String> for Channel {
             .map_err(|_| DBError::RowNotFound)
     }
 }
+
+impl Channel {
+    /// Every channel in a guild, not including threads.
+    pub async fn from_guild(db: &sqlx::PgPool, guild_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Channel,
+            "SELECT * FROM channels WHERE guild_id = $1 AND type != $2 ORDER BY position, id;",
+            guild_id,
+            ChannelType::Thread as i32
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{DBError, FromId, FromIdResult};
+
+/// A url which gets guild events POSTed to it.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct EventSubscription {
+    pub id: String,
+    pub guild_id: String,
+    pub creator_id: Option<String>,
+    pub url: String,
+    #[serde(skip)]
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub enabled: bool,
+    pub consecutive_failures: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromId<String> for EventSubscription {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(
+            EventSubscription,
+            "SELECT * FROM event_subscriptions WHERE id = $1;",
+            id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl EventSubscription {
+    pub async fn from_guild(db: &sqlx::PgPool, guild_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            EventSubscription,
+            "SELECT * FROM event_subscriptions WHERE guild_id = $1 ORDER BY id;",
+            guild_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
+
+/// One event sent, or still to be sent, to a subscription.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct EventDelivery {
+    pub id: String,
+    pub subscription_id: String,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl EventDelivery {
+    /// Newest deliveries first, starting before the `before` cursor.
+    pub async fn from_subscription(
+        db: &sqlx::PgPool,
+        subscription_id: &str,
+        before: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            EventDelivery,
+            r#"SELECT * FROM event_deliveries WHERE subscription_id = $1 AND ($2::TEXT IS NULL OR id COLLATE "C" < $2) ORDER BY id COLLATE "C" DESC LIMIT $3;"#,
+            subscription_id,
+            before,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
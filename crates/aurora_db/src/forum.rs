@@ -0,0 +1,128 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{
+    thread::{Thread, ThreadMetadata},
+    DBError, FromId, FromIdResult,
+};
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct GuildTag {
+    pub id: String,
+    pub guild_id: String,
+    pub name: String,
+}
+
+impl FromId<String> for GuildTag {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(GuildTag, "SELECT * FROM guild_tags WHERE id = $1;", id)
+            .fetch_one(db)
+            .await
+            .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl GuildTag {
+    pub async fn from_guild(db: &sqlx::PgPool, guild_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            GuildTag,
+            "SELECT * FROM guild_tags WHERE guild_id = $1 ORDER BY id;",
+            guild_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
+
+/// A thread in a forum channel, along with the tags applied to it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ForumPost {
+    #[serde(flatten)]
+    pub thread: Thread,
+    pub applied_tags: Vec<String>,
+}
+
+impl FromId<String> for ForumPost {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        let thread = Thread::from_id(db, id).await?;
+        let applied_tags = sqlx::query_scalar!(
+            "SELECT tag_id FROM thread_tags WHERE thread_id = $1 ORDER BY tag_id;",
+            &thread.channel.id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Ok(ForumPost {
+            thread,
+            applied_tags,
+        })
+    }
+}
+
+impl ForumPost {
+    /// Posts in a forum, sorted by either latest activity or creation, newest first.
+    pub async fn from_forum(
+        db: &sqlx::PgPool,
+        forum_id: &str,
+        archived: bool,
+        by_activity: bool,
+        tag_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        let metadata = sqlx::query_as!(
+            ThreadMetadata,
+            "SELECT * FROM thread_metadata WHERE id IN (SELECT id FROM channels WHERE parent_id = $1) AND archived = $2 AND ($3::TEXT IS NULL OR id IN (SELECT thread_id FROM thread_tags WHERE tag_id = $3)) ORDER BY CASE WHEN $4 THEN last_activity_at END DESC NULLS LAST, id DESC LIMIT $5;",
+            forum_id,
+            archived,
+            tag_id,
+            by_activity,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+        let threads = Thread::from_metadata(db, metadata).await?;
+
+        let ids: Vec<String> = threads.iter().map(|t| t.channel.id.clone()).collect();
+        let tags = sqlx::query!(
+            "SELECT thread_id, tag_id FROM thread_tags WHERE thread_id = ANY($1) ORDER BY tag_id;",
+            &ids
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Ok(threads
+            .into_iter()
+            .map(|thread| {
+                let applied_tags = tags
+                    .iter()
+                    .filter(|tag| tag.thread_id == thread.channel.id)
+                    .map(|tag| tag.tag_id.clone())
+                    .collect();
+
+                ForumPost {
+                    thread,
+                    applied_tags,
+                }
+            })
+            .collect())
+    }
+}
@@ -29,6 +29,8 @@ pub struct Guild {
     #[sqlx(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub permissions: Option<i64>,
+    #[sqlx(default)]
+    pub icon_url: Option<String>,
 }
 
 impl FromId<String> for Guild {
@@ -39,3 +41,16 @@ impl FromId<String> for Guild {
             .map_err(|_| DBError::RowNotFound)
     }
 }
+
+impl Guild {
+    pub async fn from_member(db: &sqlx::PgPool, user_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Guild,
+            "SELECT * FROM guilds WHERE id IN (SELECT guild_id FROM guild_members WHERE user_id = $1) ORDER BY id;",
+            user_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -40,3 +40,24 @@ impl<'a> FromId<(&'a str, &'a str)> for GuildMember {
         .map_err(|_| DBError::RowNotFound)
     }
 }
+
+impl GuildMember {
+    /// Members of a guild ordered by user id, starting after the `after` cursor.
+    pub async fn from_guild(
+        db: &sqlx::PgPool,
+        guild_id: &str,
+        after: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            GuildMember,
+            "SELECT * FROM guild_members WHERE guild_id = $1 AND ($2::TEXT IS NULL OR user_id > $2) ORDER BY user_id LIMIT $3;",
+            guild_id,
+            after,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -18,14 +18,28 @@ use sqlx::PgPool;
 pub mod account;
 pub mod account_settings;
 pub mod actor;
+pub mod application;
+pub mod attachment;
 pub mod channel;
+pub mod channel_follower;
+pub mod component;
+pub mod embed;
+pub mod event_subscription;
+pub mod forum;
 pub mod guild;
 pub mod guild_invite;
 pub mod guild_member;
 pub mod message;
+pub mod message_revision;
+pub mod poll;
+pub mod reaction;
+pub mod read_state;
 pub mod server;
 pub mod session;
+pub mod thread;
+pub mod webhook;
 
+#[derive(Debug)]
 pub enum DBError {
     RowNotFound,
     DBErr,
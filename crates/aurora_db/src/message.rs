@@ -13,10 +13,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use aurora_markdown::Document;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use sqlx::{prelude::FromRow, types::JsonValue};
 
-use crate::{DBError, FromId, FromIdResult};
+use crate::{attachment::Attachment, poll::FullPoll, DBError, FromId, FromIdResult};
 
 #[derive(FromRow, Serialize, Deserialize, Clone)]
 pub struct Message {
@@ -24,6 +26,74 @@ pub struct Message {
     pub author_id: Option<String>,
     pub channel_id: String,
     pub content: String,
+    pub flags: i32,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_guild_id: Option<String>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_channel_id: Option<String>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_message_id: Option<String>,
+    #[sqlx(default)]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_id: Option<String>,
+    #[sqlx(default)]
+    pub mentions: Vec<String>,
+    #[sqlx(default)]
+    pub mention_roles: Vec<String>,
+    #[sqlx(default)]
+    pub mention_channels: Vec<String>,
+    #[sqlx(default)]
+    pub mention_everyone: bool,
+    #[sqlx(default)]
+    pub pinned_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_id: Option<String>,
+    /// Display name of the webhook at the time of posting.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_name: Option<String>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_avatar_url: Option<String>,
+    /// The command use this message responds to.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interaction_id: Option<String>,
+    /// The message's [`Component`](crate::component::Component) tree, only bots can send them.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<JsonValue>,
+    /// The message's [`Embed`](crate::embed::Embed)s, only bots and webhooks can send them.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeds: Option<JsonValue>,
+}
+
+/// A trimmed copy of the message another message replies to.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ReferencedMessage {
+    pub id: String,
+    pub author_id: Option<String>,
+    pub content: String,
+}
+
+/// A message along with what clients need to render it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FullMessage {
+    #[serde(flatten)]
+    pub message: Message,
+    /// `None` both when the message isn't a reply and when the referenced message was deleted.
+    pub referenced_message: Option<ReferencedMessage>,
+    pub attachments: Vec<Attachment>,
+    pub poll: Option<FullPoll>,
+    /// `content` parsed, so clients don't each have to.
+    pub content_ast: Document,
 }
 
 impl FromId<String> for Message {
@@ -34,3 +104,270 @@ impl FromId<String> for Message {
             .map_err(|_| DBError::RowNotFound)
     }
 }
+
+/// Filters for searching a guild's messages.
+#[derive(Default)]
+pub struct MessageSearch<'a> {
+    /// Full-text query, in `websearch_to_tsquery` syntax.
+    pub query: Option<&'a str>,
+    pub author_id: Option<&'a str>,
+    pub channel_id: Option<&'a str>,
+    pub has_attachment: Option<bool>,
+    /// A user who must be mentioned.
+    pub mentions: Option<&'a str>,
+    /// Only messages with ids at or after this one.
+    pub min_id: Option<String>,
+    /// Only messages with ids before this one.
+    pub max_id: Option<String>,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+/// Where a page of messages is taken from.
+pub enum MessageCursor {
+    Latest,
+    Before(String),
+    After(String),
+    Around(String),
+}
+
+impl Message {
+    /// Attaches the messages referenced by `messages`, their attachments and polls.
+    pub async fn hydrate(
+        db: &sqlx::PgPool,
+        messages: Vec<Self>,
+    ) -> Result<Vec<FullMessage>, DBError> {
+        let reference_ids: Vec<String> = messages
+            .iter()
+            .filter_map(|message| message.reference_id.clone())
+            .collect();
+
+        let referenced = if reference_ids.is_empty() {
+            Vec::new()
+        } else {
+            sqlx::query_as!(
+                ReferencedMessage,
+                "SELECT id, author_id, LEFT(content, 256) AS \"content!\" FROM messages WHERE id = ANY($1);",
+                &reference_ids
+            )
+            .fetch_all(db)
+            .await
+            .map_err(|_| DBError::DBErr)?
+        };
+
+        let message_ids: Vec<String> = messages.iter().map(|message| message.id.clone()).collect();
+        let attachments = Attachment::from_messages(db, &message_ids).await?;
+        let mut polls = FullPoll::from_messages(db, &message_ids).await?;
+
+        Ok(messages
+            .into_iter()
+            .map(|message| {
+                let referenced_message = message.reference_id.as_ref().and_then(|reference_id| {
+                    referenced
+                        .iter()
+                        .find(|referenced| &referenced.id == reference_id)
+                        .cloned()
+                });
+                let attachments = attachments
+                    .iter()
+                    .filter(|attachment| attachment.message_id == message.id)
+                    .cloned()
+                    .collect();
+                let poll = polls
+                    .iter()
+                    .position(|poll| poll.poll.message_id == message.id)
+                    .map(|index| polls.swap_remove(index));
+                FullMessage {
+                    content_ast: aurora_markdown::parse(&message.content),
+                    message,
+                    referenced_message,
+                    attachments,
+                    poll,
+                }
+            })
+            .collect())
+    }
+
+    /// A page of up to `limit` messages in a channel, newest first.
+    pub async fn paginate(
+        db: &sqlx::PgPool,
+        channel_id: &str,
+        cursor: MessageCursor,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        match cursor {
+            MessageCursor::Latest => sqlx::query_as!(
+                Message,
+                "SELECT * FROM messages WHERE channel_id = $1 ORDER BY id COLLATE \"C\" DESC LIMIT $2;",
+                channel_id,
+                limit
+            )
+            .fetch_all(db)
+            .await
+            .map_err(|_| DBError::DBErr),
+            MessageCursor::Before(id) => Self::before(db, channel_id, &id, limit).await,
+            MessageCursor::After(id) => Self::after(db, channel_id, &id, limit).await,
+            MessageCursor::Around(id) => {
+                // the message itself is counted towards the newer half
+                let older_limit = limit / 2;
+                let newer_limit = limit - older_limit;
+
+                let mut messages = sqlx::query_as!(
+                    Message,
+                    "SELECT * FROM messages WHERE channel_id = $1 AND id COLLATE \"C\" >= $2 ORDER BY id COLLATE \"C\" LIMIT $3;",
+                    channel_id,
+                    &id,
+                    newer_limit
+                )
+                .fetch_all(db)
+                .await
+                .map_err(|_| DBError::DBErr)?;
+                messages.reverse();
+                messages.extend(Self::before(db, channel_id, &id, older_limit).await?);
+
+                Ok(messages)
+            }
+        }
+    }
+
+    /// Searches a guild's messages, best matches first, returning the total number of matches too.
+    pub async fn search(
+        db: &sqlx::PgPool,
+        guild_id: &str,
+        search: &MessageSearch<'_>,
+    ) -> Result<(i64, Vec<Self>), DBError> {
+        let total = sqlx::query!(
+            "SELECT COUNT(*) AS \"count!\" FROM messages
+            INNER JOIN channels ON channels.id = messages.channel_id
+            WHERE channels.guild_id = $1
+            AND ($2::TEXT IS NULL OR to_tsvector('english', messages.content) @@ websearch_to_tsquery('english', $2))
+            AND ($3::TEXT IS NULL OR messages.author_id = $3)
+            AND ($4::TEXT IS NULL OR messages.channel_id = $4)
+            AND ($5::BOOLEAN IS NULL OR EXISTS (SELECT 1 FROM attachments WHERE attachments.message_id = messages.id) = $5)
+            AND ($6::TEXT IS NULL OR messages.mentions @> ARRAY[$6])
+            AND ($7::TEXT IS NULL OR messages.id COLLATE \"C\" >= $7)
+            AND ($8::TEXT IS NULL OR messages.id COLLATE \"C\" < $8);",
+            guild_id,
+            search.query,
+            search.author_id,
+            search.channel_id,
+            search.has_attachment,
+            search.mentions,
+            search.min_id,
+            search.max_id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::DBErr)?
+        .count;
+
+        let messages = sqlx::query_as!(
+            Message,
+            "SELECT messages.* FROM messages
+            INNER JOIN channels ON channels.id = messages.channel_id
+            WHERE channels.guild_id = $1
+            AND ($2::TEXT IS NULL OR to_tsvector('english', messages.content) @@ websearch_to_tsquery('english', $2))
+            AND ($3::TEXT IS NULL OR messages.author_id = $3)
+            AND ($4::TEXT IS NULL OR messages.channel_id = $4)
+            AND ($5::BOOLEAN IS NULL OR EXISTS (SELECT 1 FROM attachments WHERE attachments.message_id = messages.id) = $5)
+            AND ($6::TEXT IS NULL OR messages.mentions @> ARRAY[$6])
+            AND ($7::TEXT IS NULL OR messages.id COLLATE \"C\" >= $7)
+            AND ($8::TEXT IS NULL OR messages.id COLLATE \"C\" < $8)
+            ORDER BY ts_rank_cd(to_tsvector('english', messages.content), websearch_to_tsquery('english', $2)) DESC NULLS LAST,
+            messages.id COLLATE \"C\" DESC
+            OFFSET $9 LIMIT $10;",
+            guild_id,
+            search.query,
+            search.author_id,
+            search.channel_id,
+            search.has_attachment,
+            search.mentions,
+            search.min_id,
+            search.max_id,
+            search.offset,
+            search.limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Ok((total, messages))
+    }
+
+    /// Pinned messages in a channel, most recently pinned first.
+    pub async fn pinned(db: &sqlx::PgPool, channel_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Message,
+            "SELECT * FROM messages WHERE channel_id = $1 AND pinned_at IS NOT NULL ORDER BY pinned_at DESC;",
+            channel_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    /// Messages mentioning a user, either directly or through @everyone in guilds they're in, newest first.
+    pub async fn mentioning(
+        db: &sqlx::PgPool,
+        user_id: &str,
+        before: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Message,
+            "SELECT messages.* FROM messages
+            INNER JOIN channels ON channels.id = messages.channel_id
+            INNER JOIN guild_members ON guild_members.guild_id = channels.guild_id AND guild_members.user_id = $1
+            WHERE (messages.mentions @> ARRAY[$1] OR messages.mention_everyone)
+            AND messages.author_id IS DISTINCT FROM $1
+            AND ($2::TEXT IS NULL OR messages.id COLLATE \"C\" < $2)
+            ORDER BY messages.id COLLATE \"C\" DESC LIMIT $3;",
+            user_id,
+            before,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    async fn before(
+        db: &sqlx::PgPool,
+        channel_id: &str,
+        id: &str,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Message,
+            "SELECT * FROM messages WHERE channel_id = $1 AND id COLLATE \"C\" < $2 ORDER BY id COLLATE \"C\" DESC LIMIT $3;",
+            channel_id,
+            id,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    async fn after(
+        db: &sqlx::PgPool,
+        channel_id: &str,
+        id: &str,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        // take the oldest messages after the cursor, then flip them to match the other modes
+        let mut messages = sqlx::query_as!(
+            Message,
+            "SELECT * FROM messages WHERE channel_id = $1 AND id COLLATE \"C\" > $2 ORDER BY id COLLATE \"C\" LIMIT $3;",
+            channel_id,
+            id,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+        messages.reverse();
+
+        Ok(messages)
+    }
+}
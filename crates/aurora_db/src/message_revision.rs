@@ -0,0 +1,42 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::DBError;
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct MessageRevision {
+    pub id: String,
+    pub message_id: String,
+    pub content: String,
+    pub replaced_at: DateTime<Utc>,
+}
+
+impl MessageRevision {
+    /// Every previous version of a message, most recent first.
+    pub async fn from_message(db: &sqlx::PgPool, message_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            MessageRevision,
+            "SELECT * FROM message_revisions WHERE message_id = $1 ORDER BY replaced_at DESC, id DESC;",
+            message_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgConnection};
+
+use crate::DBError;
+
+/// A poll attached to a message.
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Poll {
+    pub message_id: String,
+    pub guild_id: String,
+    pub question: String,
+    pub allow_multiselect: bool,
+    pub expires_at: DateTime<Utc>,
+    /// Set once the poll has ended and its results are final.
+    pub closed: bool,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct PollAnswer {
+    #[serde(skip)]
+    pub message_id: String,
+    pub answer_id: i32,
+    pub text: String,
+    pub vote_count: i32,
+}
+
+/// A poll along with its answers and their tallies.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FullPoll {
+    #[serde(flatten)]
+    pub poll: Poll,
+    pub answers: Vec<PollAnswer>,
+}
+
+/// What voting or unvoting did.
+#[derive(Debug, PartialEq)]
+pub enum VoteResult {
+    Changed,
+    /// The user had already voted that way.
+    Unchanged,
+    Closed,
+    AnswerNotFound,
+}
+
+impl Poll {
+    /// Whether votes can no longer change, even if the poll hasn't been closed yet.
+    pub fn has_ended(&self) -> bool {
+        self.closed || self.expires_at <= Utc::now()
+    }
+
+    /// Locks a poll for voting, so votes can't slip in after it closes or race each other.
+    async fn lock(
+        tx: &mut PgConnection,
+        message_id: &str,
+        answer_id: i32,
+    ) -> Result<Result<Self, VoteResult>, DBError> {
+        let poll = sqlx::query_as!(
+            Poll,
+            "SELECT * FROM polls WHERE message_id = $1 FOR UPDATE;",
+            message_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?
+        .ok_or(DBError::RowNotFound)?;
+
+        if poll.has_ended() {
+            return Ok(Err(VoteResult::Closed));
+        }
+
+        let answer = sqlx::query!(
+            "SELECT answer_id FROM poll_answers WHERE message_id = $1 AND answer_id = $2;",
+            message_id,
+            answer_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Ok(answer.map(|_| poll).ok_or(VoteResult::AnswerNotFound))
+    }
+
+    /// Votes for an answer, taking back the user's other vote unless the poll allows several.
+    pub async fn vote(
+        db: &sqlx::PgPool,
+        message_id: &str,
+        answer_id: i32,
+        user_id: &str,
+    ) -> Result<VoteResult, DBError> {
+        let mut tx = db.begin().await.map_err(|_| DBError::DBErr)?;
+        let poll = match Self::lock(&mut tx, message_id, answer_id).await? {
+            Ok(poll) => poll,
+            Err(result) => return Ok(result),
+        };
+
+        let voted = sqlx::query!(
+            "INSERT INTO poll_votes (message_id, answer_id, user_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING;",
+            message_id,
+            answer_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?
+        .rows_affected();
+        if voted == 0 {
+            return Ok(VoteResult::Unchanged);
+        }
+
+        if !poll.allow_multiselect {
+            let replaced: Vec<i32> = sqlx::query!(
+                "DELETE FROM poll_votes WHERE message_id = $1 AND user_id = $2 AND answer_id <> $3 RETURNING answer_id;",
+                message_id,
+                user_id,
+                answer_id
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|_| DBError::DBErr)?
+            .into_iter()
+            .map(|row| row.answer_id)
+            .collect();
+
+            sqlx::query!(
+                "UPDATE poll_answers SET vote_count = vote_count - 1 WHERE message_id = $1 AND answer_id = ANY($2);",
+                message_id,
+                &replaced
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| DBError::DBErr)?;
+        }
+
+        sqlx::query!(
+            "UPDATE poll_answers SET vote_count = vote_count + 1 WHERE message_id = $1 AND answer_id = $2;",
+            message_id,
+            answer_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        tx.commit().await.map_err(|_| DBError::DBErr)?;
+        Ok(VoteResult::Changed)
+    }
+
+    pub async fn unvote(
+        db: &sqlx::PgPool,
+        message_id: &str,
+        answer_id: i32,
+        user_id: &str,
+    ) -> Result<VoteResult, DBError> {
+        let mut tx = db.begin().await.map_err(|_| DBError::DBErr)?;
+        if let Err(result) = Self::lock(&mut tx, message_id, answer_id).await? {
+            return Ok(result);
+        }
+
+        let unvoted = sqlx::query!(
+            "DELETE FROM poll_votes WHERE message_id = $1 AND answer_id = $2 AND user_id = $3;",
+            message_id,
+            answer_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?
+        .rows_affected();
+        if unvoted == 0 {
+            return Ok(VoteResult::Unchanged);
+        }
+
+        sqlx::query!(
+            "UPDATE poll_answers SET vote_count = vote_count - 1 WHERE message_id = $1 AND answer_id = $2;",
+            message_id,
+            answer_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        tx.commit().await.map_err(|_| DBError::DBErr)?;
+        Ok(VoteResult::Changed)
+    }
+
+    /// Closes every poll which has run out of time, returning their final results.
+    pub async fn close_expired(db: &sqlx::PgPool) -> Result<Vec<FullPoll>, DBError> {
+        let polls = sqlx::query_as!(
+            Poll,
+            "UPDATE polls SET closed = true WHERE NOT closed AND expires_at <= now() RETURNING *;"
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        FullPoll::from_polls(db, polls).await
+    }
+}
+
+impl FullPoll {
+    pub async fn from_message(db: &sqlx::PgPool, message_id: &str) -> Result<Self, DBError> {
+        Self::from_messages(db, &[message_id.to_string()])
+            .await?
+            .pop()
+            .ok_or(DBError::RowNotFound)
+    }
+
+    /// The polls attached to any of `message_ids`.
+    pub async fn from_messages(
+        db: &sqlx::PgPool,
+        message_ids: &[String],
+    ) -> Result<Vec<Self>, DBError> {
+        let polls = sqlx::query_as!(
+            Poll,
+            "SELECT * FROM polls WHERE message_id = ANY($1);",
+            message_ids
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Self::from_polls(db, polls).await
+    }
+
+    async fn from_polls(db: &sqlx::PgPool, polls: Vec<Poll>) -> Result<Vec<Self>, DBError> {
+        if polls.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let message_ids: Vec<String> = polls.iter().map(|poll| poll.message_id.clone()).collect();
+        let mut answers: HashMap<String, Vec<PollAnswer>> = HashMap::new();
+        for answer in sqlx::query_as!(
+            PollAnswer,
+            "SELECT * FROM poll_answers WHERE message_id = ANY($1) ORDER BY answer_id;",
+            &message_ids
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?
+        {
+            answers
+                .entry(answer.message_id.clone())
+                .or_default()
+                .push(answer);
+        }
+
+        Ok(polls
+            .into_iter()
+            .map(|poll| FullPoll {
+                answers: answers.remove(&poll.message_id).unwrap_or_default(),
+                poll,
+            })
+            .collect())
+    }
+}
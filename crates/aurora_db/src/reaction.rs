@@ -0,0 +1,71 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::DBError;
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Reaction {
+    pub message_id: String,
+    pub user_id: String,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Reaction {
+    /// Users who reacted to a message with `emoji`, ordered by user id after the `after` cursor.
+    pub async fn from_emoji(
+        db: &sqlx::PgPool,
+        message_id: &str,
+        emoji: &str,
+        after: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Reaction,
+            "SELECT * FROM message_reactions WHERE message_id = $1 AND emoji = $2 AND ($3::TEXT IS NULL OR user_id > $3) ORDER BY user_id LIMIT $4;",
+            message_id,
+            emoji,
+            after,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ReactionCount {
+    pub message_id: String,
+    pub emoji: String,
+    pub count: i32,
+}
+
+impl ReactionCount {
+    pub async fn from_message(db: &sqlx::PgPool, message_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            ReactionCount,
+            "SELECT * FROM message_reaction_counts WHERE message_id = $1 ORDER BY emoji;",
+            message_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
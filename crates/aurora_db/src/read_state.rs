@@ -0,0 +1,75 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::DBError;
+
+/// Unread counts stop here, clients only need to know it's "a lot".
+pub const MAX_UNREAD_COUNT: i64 = 100;
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ReadState {
+    pub user_id: String,
+    pub channel_id: String,
+    pub last_message_id: String,
+    pub updated_at: DateTime<Utc>,
+    /// Messages from other users after `last_message_id`, up to [`MAX_UNREAD_COUNT`].
+    pub unread_count: i64,
+    /// Messages after `last_message_id` which mention the user.
+    pub mention_count: i64,
+}
+
+impl ReadState {
+    /// Marks everything up to and including `message_id` as read.
+    ///
+    /// Acking an older message moves the read state back, letting clients mark messages unread.
+    pub async fn ack(
+        db: &sqlx::PgPool,
+        user_id: &str,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<Self, DBError> {
+        sqlx::query_as!(
+            ReadState,
+            r#"WITH read_state AS (
+                INSERT INTO read_states (user_id, channel_id, last_message_id) VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, channel_id) DO UPDATE SET last_message_id = $3, updated_at = now()
+                RETURNING *
+            )
+            SELECT
+                read_state.*,
+                (SELECT COUNT(*) FROM (
+                    SELECT 1 FROM messages
+                    WHERE channel_id = $2 AND id COLLATE "C" > $3 AND author_id IS DISTINCT FROM $1
+                    LIMIT $4
+                ) unread) AS "unread_count!",
+                (SELECT COUNT(*) FROM messages
+                    WHERE channel_id = $2 AND id COLLATE "C" > $3 AND author_id IS DISTINCT FROM $1
+                    AND ($1 = ANY(mentions) OR mention_everyone)
+                ) AS "mention_count!"
+            FROM read_state;"#,
+            user_id,
+            channel_id,
+            message_id,
+            MAX_UNREAD_COUNT
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{channel::Channel, DBError, FromId, FromIdResult};
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ThreadMetadata {
+    pub id: String,
+    pub message_id: Option<String>,
+    pub owner_id: String,
+    pub archived: bool,
+    pub auto_archive_duration: i32,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub last_activity_at: DateTime<Utc>,
+    pub solved: bool,
+}
+
+impl FromId<String> for ThreadMetadata {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(
+            ThreadMetadata,
+            "SELECT * FROM thread_metadata WHERE id = $1;",
+            id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+/// A thread channel along with its metadata.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Thread {
+    #[serde(flatten)]
+    pub channel: Channel,
+    pub thread_metadata: ThreadMetadata,
+}
+
+impl FromId<String> for Thread {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        let channel = Channel::from_id(db, id.clone()).await?;
+        let thread_metadata = ThreadMetadata::from_id(db, id).await?;
+
+        Ok(Thread {
+            channel,
+            thread_metadata,
+        })
+    }
+}
+
+impl Thread {
+    /// Threads under `parent_id`, most recently active first.
+    pub async fn from_parent(
+        db: &sqlx::PgPool,
+        parent_id: &str,
+        archived: bool,
+    ) -> Result<Vec<Self>, DBError> {
+        let metadata = sqlx::query_as!(
+            ThreadMetadata,
+            "SELECT * FROM thread_metadata WHERE id IN (SELECT id FROM channels WHERE parent_id = $1) AND archived = $2 ORDER BY last_activity_at DESC;",
+            parent_id,
+            archived
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)?;
+
+        Self::from_metadata(db, metadata).await
+    }
+
+    /// Pairs each piece of metadata with its channel, keeping the metadata's order.
+    pub async fn from_metadata(
+        db: &sqlx::PgPool,
+        metadata: Vec<ThreadMetadata>,
+    ) -> Result<Vec<Self>, DBError> {
+        let ids: Vec<String> = metadata.iter().map(|m| m.id.clone()).collect();
+        let mut channels =
+            sqlx::query_as!(Channel, "SELECT * FROM channels WHERE id = ANY($1);", &ids)
+                .fetch_all(db)
+                .await
+                .map_err(|_| DBError::DBErr)?;
+
+        Ok(metadata
+            .into_iter()
+            .filter_map(|thread_metadata| {
+                let pos = channels.iter().position(|c| c.id == thread_metadata.id)?;
+                Some(Thread {
+                    channel: channels.swap_remove(pos),
+                    thread_metadata,
+                })
+            })
+            .collect())
+    }
+}
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct ThreadMember {
+    pub thread_id: String,
+    pub user_id: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+impl<'a> FromId<(&'a str, &'a str)> for ThreadMember {
+    async fn from_id(db: &sqlx::PgPool, id: (&'a str, &'a str)) -> FromIdResult<Self> {
+        sqlx::query_as!(
+            ThreadMember,
+            "SELECT * FROM thread_members WHERE thread_id = $1 AND user_id = $2;",
+            id.0,
+            id.1
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl ThreadMember {
+    pub async fn from_thread(db: &sqlx::PgPool, thread_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            ThreadMember,
+            "SELECT * FROM thread_members WHERE thread_id = $1 ORDER BY joined_at;",
+            thread_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{DBError, FromId, FromIdResult};
+
+#[derive(FromRow, Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub guild_id: String,
+    pub channel_id: String,
+    pub creator_id: Option<String>,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromId<String> for Webhook {
+    async fn from_id(db: &sqlx::PgPool, id: String) -> FromIdResult<Self> {
+        sqlx::query_as!(Webhook, "SELECT * FROM webhooks WHERE id = $1;", id)
+            .fetch_one(db)
+            .await
+            .map_err(|_| DBError::RowNotFound)
+    }
+}
+
+impl Webhook {
+    pub async fn from_channel(db: &sqlx::PgPool, channel_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Webhook,
+            "SELECT * FROM webhooks WHERE channel_id = $1 ORDER BY id;",
+            channel_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+
+    pub async fn from_guild(db: &sqlx::PgPool, guild_id: &str) -> Result<Vec<Self>, DBError> {
+        sqlx::query_as!(
+            Webhook,
+            "SELECT * FROM webhooks WHERE guild_id = $1 ORDER BY id;",
+            guild_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| DBError::DBErr)
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Fixtures shared by the database tests, inserted with plain queries so they don't depend on
+//! the code under test.
+
+// every test file only uses some of these
+#![allow(dead_code)]
+
+use sqlx::PgPool;
+
+pub async fn seed_actor(db: &PgPool, username: &str) -> String {
+    let id = uuid7::uuid7().to_string();
+    sqlx::query("INSERT INTO actors (id, username) VALUES ($1, $2);")
+        .bind(&id)
+        .bind(username)
+        .execute(db)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO accounts (id, actor_id) VALUES ($1, $1);")
+        .bind(&id)
+        .execute(db)
+        .await
+        .unwrap();
+    id
+}
+
+/// A guild with one channel, returning both ids.
+pub async fn seed_guild(db: &PgPool, owner_id: &str) -> (String, String) {
+    let guild_id = uuid7::uuid7().to_string();
+    sqlx::query("INSERT INTO guilds (id, owner_id, name) VALUES ($1, $2, 'guild');")
+        .bind(&guild_id)
+        .bind(owner_id)
+        .execute(db)
+        .await
+        .unwrap();
+
+    let channel_id = seed_channel(db, &guild_id).await;
+    (guild_id, channel_id)
+}
+
+pub async fn seed_channel(db: &PgPool, guild_id: &str) -> String {
+    let channel_id = uuid7::uuid7().to_string();
+    sqlx::query(
+        "INSERT INTO channels (id, guild_id, name, position) VALUES ($1, $2, 'general', 0);",
+    )
+    .bind(&channel_id)
+    .bind(guild_id)
+    .execute(db)
+    .await
+    .unwrap();
+    channel_id
+}
+
+pub async fn send(db: &PgPool, channel_id: &str, author_id: &str, content: &str) -> String {
+    let id = uuid7::uuid7().to_string();
+    sqlx::query(
+        "INSERT INTO messages (id, author_id, channel_id, content) VALUES ($1, $2, $3, $4);",
+    )
+    .bind(&id)
+    .bind(author_id)
+    .bind(channel_id)
+    .bind(content)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
@@ -0,0 +1,149 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::message::{Message, MessageCursor};
+use sqlx::PgPool;
+
+/// Creates a channel holding `count` messages, returning their ids oldest first.
+async fn seed_channel(db: &PgPool, count: usize) -> (String, Vec<String>) {
+    let channel_id = uuid7::uuid7().to_string();
+    sqlx::query("INSERT INTO channels (id, name, position) VALUES ($1, 'general', 0);")
+        .bind(&channel_id)
+        .execute(db)
+        .await
+        .unwrap();
+
+    let mut ids = Vec::with_capacity(count);
+    for i in 0..count {
+        let id = uuid7::uuid7().to_string();
+        sqlx::query("INSERT INTO messages (id, channel_id, content) VALUES ($1, $2, $3);")
+            .bind(&id)
+            .bind(&channel_id)
+            .bind(format!("message {i}"))
+            .execute(db)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+
+    (channel_id, ids)
+}
+
+fn ids(messages: Vec<Message>) -> Vec<String> {
+    messages.into_iter().map(|m| m.id).collect()
+}
+
+fn newest_first(ids: &[String]) -> Vec<String> {
+    ids.iter().rev().cloned().collect()
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn latest_returns_newest_messages_first(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 10).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Latest, 4)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(page), newest_first(&sent[6..]));
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn latest_with_empty_channel(db: PgPool) {
+    let (channel_id, _) = seed_channel(&db, 0).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Latest, 30)
+        .await
+        .unwrap();
+
+    assert!(page.is_empty());
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn before_excludes_cursor(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 10).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Before(sent[5].clone()), 3)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(page), newest_first(&sent[2..5]));
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn before_oldest_message_is_empty(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 5).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Before(sent[0].clone()), 30)
+        .await
+        .unwrap();
+
+    assert!(page.is_empty());
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn after_returns_messages_right_after_cursor(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 10).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::After(sent[2].clone()), 3)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(page), newest_first(&sent[3..6]));
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn around_centers_on_cursor(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 10).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Around(sent[5].clone()), 5)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(page), newest_first(&sent[3..8]));
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn pages_walk_the_whole_history(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 12).await;
+
+    let mut seen = Vec::new();
+    let mut cursor = MessageCursor::Latest;
+    loop {
+        let page = Message::paginate(&db, &channel_id, cursor, 5)
+            .await
+            .unwrap();
+        let page = ids(page);
+        let Some(oldest) = page.last().cloned() else {
+            break;
+        };
+        seen.extend(page);
+        cursor = MessageCursor::Before(oldest);
+    }
+
+    assert_eq!(seen, newest_first(&sent));
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn other_channels_are_excluded(db: PgPool) {
+    let (channel_id, sent) = seed_channel(&db, 3).await;
+    seed_channel(&db, 3).await;
+
+    let page = Message::paginate(&db, &channel_id, MessageCursor::Latest, 30)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(page), newest_first(&sent));
+}
@@ -0,0 +1,166 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::message::{Message, MessageSearch};
+use sqlx::PgPool;
+
+mod common;
+
+use common::{seed_actor, seed_channel, seed_guild, send};
+
+fn search(query: Option<&str>) -> MessageSearch<'_> {
+    MessageSearch {
+        query,
+        limit: 25,
+        ..Default::default()
+    }
+}
+
+fn contents(messages: Vec<Message>) -> Vec<String> {
+    messages.into_iter().map(|m| m.content).collect()
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn matches_word_forms_and_ranks_better_matches_first(db: PgPool) {
+    let actor = seed_actor(&db, "actor").await;
+    let (guild_id, channel_id) = seed_guild(&db, &actor).await;
+    send(&db, &channel_id, &actor, "the deploy went fine").await;
+    send(
+        &db,
+        &channel_id,
+        &actor,
+        "deploying again, deployed twice, deploys everywhere",
+    )
+    .await;
+    send(&db, &channel_id, &actor, "lunch?").await;
+
+    let (total, messages) = Message::search(&db, &guild_id, &search(Some("deploy")))
+        .await
+        .unwrap();
+
+    assert_eq!(total, 2);
+    assert_eq!(
+        contents(messages),
+        [
+            "deploying again, deployed twice, deploys everywhere",
+            "the deploy went fine"
+        ]
+    );
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn only_searches_the_given_guild(db: PgPool) {
+    let actor = seed_actor(&db, "actor").await;
+    let (guild_id, channel_id) = seed_guild(&db, &actor).await;
+    let (_, other_channel_id) = seed_guild(&db, &actor).await;
+    send(&db, &channel_id, &actor, "secret plans").await;
+    send(&db, &other_channel_id, &actor, "other secret plans").await;
+
+    let (total, messages) = Message::search(&db, &guild_id, &search(Some("secret")))
+        .await
+        .unwrap();
+
+    assert_eq!(total, 1);
+    assert_eq!(contents(messages), ["secret plans"]);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn filters_by_author_channel_and_mentions(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let bob = seed_actor(&db, "bob").await;
+    let (guild_id, general) = seed_guild(&db, &alice).await;
+    let random = seed_channel(&db, &guild_id).await;
+    send(&db, &general, &alice, "hello from alice").await;
+    send(&db, &random, &bob, "hello from bob").await;
+    let mentioning = send(&db, &random, &alice, "hello bob").await;
+    sqlx::query("UPDATE messages SET mentions = ARRAY[$1] WHERE id = $2;")
+        .bind(&bob)
+        .bind(&mentioning)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let by_author = MessageSearch {
+        author_id: Some(&bob),
+        ..search(Some("hello"))
+    };
+    let (_, messages) = Message::search(&db, &guild_id, &by_author).await.unwrap();
+    assert_eq!(contents(messages), ["hello from bob"]);
+
+    let in_channel = MessageSearch {
+        channel_id: Some(&general),
+        ..search(None)
+    };
+    let (_, messages) = Message::search(&db, &guild_id, &in_channel).await.unwrap();
+    assert_eq!(contents(messages), ["hello from alice"]);
+
+    let mentioning_bob = MessageSearch {
+        mentions: Some(&bob),
+        ..search(None)
+    };
+    let (_, messages) = Message::search(&db, &guild_id, &mentioning_bob)
+        .await
+        .unwrap();
+    assert_eq!(contents(messages), ["hello bob"]);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn filters_by_attachments(db: PgPool) {
+    let actor = seed_actor(&db, "actor").await;
+    let (guild_id, channel_id) = seed_guild(&db, &actor).await;
+    send(&db, &channel_id, &actor, "just text").await;
+    let with_file = send(&db, &channel_id, &actor, "see attached").await;
+    sqlx::query(
+        "INSERT INTO attachments (id, message_id, filename, content_type, size, blob_key) VALUES ($1, $2, 'a.txt', 'text/plain', 1, 'k');",
+    )
+    .bind(uuid7::uuid7().to_string())
+    .bind(&with_file)
+    .execute(&db)
+    .await
+    .unwrap();
+
+    for (has_attachment, expected) in [(true, "see attached"), (false, "just text")] {
+        let filter = MessageSearch {
+            has_attachment: Some(has_attachment),
+            ..search(None)
+        };
+        let (total, messages) = Message::search(&db, &guild_id, &filter).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(contents(messages), [expected]);
+    }
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn filters_by_id_range_and_paginates(db: PgPool) {
+    let actor = seed_actor(&db, "actor").await;
+    let (guild_id, channel_id) = seed_guild(&db, &actor).await;
+    let mut ids = Vec::new();
+    for i in 0..6 {
+        ids.push(send(&db, &channel_id, &actor, &format!("update {i}")).await);
+    }
+
+    let range = MessageSearch {
+        min_id: Some(ids[1].clone()),
+        max_id: Some(ids[5].clone()),
+        offset: 1,
+        limit: 2,
+        ..search(Some("update"))
+    };
+    let (total, messages) = Message::search(&db, &guild_id, &range).await.unwrap();
+
+    // equally ranked matches fall back to newest first
+    assert_eq!(total, 4);
+    assert_eq!(contents(messages), ["update 3", "update 2"]);
+}
@@ -0,0 +1,183 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::{
+    message::Message,
+    poll::{FullPoll, Poll, VoteResult},
+    DBError, FromId,
+};
+use sqlx::PgPool;
+
+mod common;
+
+use common::{seed_actor, seed_guild, send};
+
+/// A message with a yes or no poll on it, closing after `expires_in` seconds.
+async fn seed_poll(
+    db: &PgPool,
+    author_id: &str,
+    allow_multiselect: bool,
+    expires_in: i32,
+) -> String {
+    let (guild_id, channel_id) = seed_guild(db, author_id).await;
+    let message_id = send(db, &channel_id, author_id, "").await;
+    sqlx::query(
+        "INSERT INTO polls (message_id, guild_id, question, allow_multiselect, expires_at)
+        VALUES ($1, $2, 'pizza?', $3, now() + make_interval(secs => $4));",
+    )
+    .bind(&message_id)
+    .bind(&guild_id)
+    .bind(allow_multiselect)
+    .bind(expires_in)
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO poll_answers (message_id, answer_id, text) VALUES ($1, 1, 'yes'), ($1, 2, 'no');",
+    )
+    .bind(&message_id)
+    .execute(db)
+    .await
+    .unwrap();
+    message_id
+}
+
+async fn tallies(db: &PgPool, message_id: &str) -> Vec<i32> {
+    FullPoll::from_message(db, message_id)
+        .await
+        .unwrap()
+        .answers
+        .iter()
+        .map(|answer| answer.vote_count)
+        .collect()
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn single_choice_votes_replace_each_other(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let bob = seed_actor(&db, "bob").await;
+    let poll = seed_poll(&db, &alice, false, 3600).await;
+
+    assert_eq!(
+        Poll::vote(&db, &poll, 1, &alice).await.unwrap(),
+        VoteResult::Changed
+    );
+    assert_eq!(
+        Poll::vote(&db, &poll, 1, &bob).await.unwrap(),
+        VoteResult::Changed
+    );
+    assert_eq!(tallies(&db, &poll).await, vec![2, 0]);
+
+    assert_eq!(
+        Poll::vote(&db, &poll, 2, &alice).await.unwrap(),
+        VoteResult::Changed
+    );
+    assert_eq!(
+        Poll::vote(&db, &poll, 2, &alice).await.unwrap(),
+        VoteResult::Unchanged
+    );
+    assert_eq!(tallies(&db, &poll).await, vec![1, 1]);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn multiple_choice_votes_add_up(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let poll = seed_poll(&db, &alice, true, 3600).await;
+
+    Poll::vote(&db, &poll, 1, &alice).await.unwrap();
+    Poll::vote(&db, &poll, 2, &alice).await.unwrap();
+    assert_eq!(tallies(&db, &poll).await, vec![1, 1]);
+
+    assert_eq!(
+        Poll::unvote(&db, &poll, 1, &alice).await.unwrap(),
+        VoteResult::Changed
+    );
+    assert_eq!(
+        Poll::unvote(&db, &poll, 1, &alice).await.unwrap(),
+        VoteResult::Unchanged
+    );
+    assert_eq!(tallies(&db, &poll).await, vec![0, 1]);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn refuses_votes_which_cant_count(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let open = seed_poll(&db, &alice, false, 3600).await;
+    let ended = seed_poll(&db, &alice, false, -1).await;
+
+    assert_eq!(
+        Poll::vote(&db, &open, 3, &alice).await.unwrap(),
+        VoteResult::AnswerNotFound
+    );
+    assert_eq!(
+        Poll::vote(&db, &ended, 1, &alice).await.unwrap(),
+        VoteResult::Closed
+    );
+    assert_eq!(
+        Poll::unvote(&db, &ended, 1, &alice).await.unwrap(),
+        VoteResult::Closed
+    );
+    assert!(matches!(
+        Poll::vote(&db, "missing", 1, &alice).await,
+        Err(DBError::RowNotFound)
+    ));
+    assert_eq!(tallies(&db, &open).await, vec![0, 0]);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn closes_expired_polls_once(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let open = seed_poll(&db, &alice, false, 3600).await;
+    let expiring = seed_poll(&db, &alice, false, 3600).await;
+    Poll::vote(&db, &expiring, 2, &alice).await.unwrap();
+    sqlx::query("UPDATE polls SET expires_at = now() WHERE message_id = $1;")
+        .bind(&expiring)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let closed = Poll::close_expired(&db).await.unwrap();
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].poll.message_id, expiring);
+    assert!(closed[0].poll.closed);
+    let results: Vec<(i32, i32)> = closed[0]
+        .answers
+        .iter()
+        .map(|answer| (answer.answer_id, answer.vote_count))
+        .collect();
+    assert_eq!(results, vec![(1, 0), (2, 1)]);
+
+    assert!(Poll::close_expired(&db).await.unwrap().is_empty());
+    assert!(
+        !FullPoll::from_message(&db, &open)
+            .await
+            .unwrap()
+            .poll
+            .closed
+    );
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn hydrates_polls_onto_their_messages(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let poll = seed_poll(&db, &alice, false, 3600).await;
+    let message = Message::from_id(&db, poll.clone()).await.unwrap();
+
+    let hydrated = Message::hydrate(&db, vec![message]).await.unwrap();
+    let poll = hydrated[0].poll.as_ref().unwrap();
+    assert_eq!(poll.poll.question, "pizza?");
+    assert_eq!(poll.answers.len(), 2);
+    assert_eq!(poll.answers[0].text, "yes");
+}
@@ -0,0 +1,95 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_db::read_state::{ReadState, MAX_UNREAD_COUNT};
+use sqlx::PgPool;
+
+mod common;
+
+use common::{seed_actor, seed_guild, send};
+
+async fn mention(db: &PgPool, message_id: &str, user_id: &str) {
+    sqlx::query("UPDATE messages SET mentions = ARRAY[$1] WHERE id = $2;")
+        .bind(user_id)
+        .bind(message_id)
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn counts_unread_messages_and_mentions_after_the_ack(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let bob = seed_actor(&db, "bob").await;
+    let (_, channel_id) = seed_guild(&db, &alice).await;
+    let first = send(&db, &channel_id, &bob, "first").await;
+    let read = send(&db, &channel_id, &bob, "read").await;
+    mention(&db, &first, &alice).await;
+    send(&db, &channel_id, &alice, "my own message").await;
+    let unread = send(&db, &channel_id, &bob, "unread").await;
+    mention(&db, &unread, &alice).await;
+    send(&db, &channel_id, &bob, "also unread").await;
+
+    let state = ReadState::ack(&db, &alice, &channel_id, &read)
+        .await
+        .unwrap();
+
+    assert_eq!(state.last_message_id, read);
+    assert_eq!(state.unread_count, 2);
+    assert_eq!(state.mention_count, 1);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn acking_an_older_message_marks_newer_ones_unread(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let bob = seed_actor(&db, "bob").await;
+    let (_, channel_id) = seed_guild(&db, &alice).await;
+    let older = send(&db, &channel_id, &bob, "older").await;
+    let newer = send(&db, &channel_id, &bob, "newer").await;
+
+    let state = ReadState::ack(&db, &alice, &channel_id, &newer)
+        .await
+        .unwrap();
+    assert_eq!(state.unread_count, 0);
+
+    let state = ReadState::ack(&db, &alice, &channel_id, &older)
+        .await
+        .unwrap();
+    assert_eq!(state.last_message_id, older);
+    assert_eq!(state.unread_count, 1);
+
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM read_states;")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+}
+
+#[sqlx::test(migrations = "../../migrations")]
+async fn caps_unread_counts(db: PgPool) {
+    let alice = seed_actor(&db, "alice").await;
+    let bob = seed_actor(&db, "bob").await;
+    let (_, channel_id) = seed_guild(&db, &alice).await;
+    let read = send(&db, &channel_id, &bob, "read").await;
+    for i in 0..MAX_UNREAD_COUNT + 5 {
+        send(&db, &channel_id, &bob, &format!("spam {i}")).await;
+    }
+
+    let state = ReadState::ack(&db, &alice, &channel_id, &read)
+        .await
+        .unwrap();
+
+    assert_eq!(state.unread_count, MAX_UNREAD_COUNT);
+}
@@ -0,0 +1,36 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![no_main]
+
+use aurora_markdown::{escape, parse, Document, Node, AST_VERSION};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    let document = parse(content);
+    assert_eq!(document.version, AST_VERSION);
+
+    let json = serde_json::to_string(&document).unwrap();
+    assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+
+    if !content.is_empty() {
+        assert_eq!(
+            parse(&escape(content)).nodes,
+            vec![Node::Text {
+                text: content.to_string()
+            }]
+        );
+    }
+});
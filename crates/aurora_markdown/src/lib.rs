@@ -0,0 +1,112 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Aurora's markdown dialect, parsed once on the server so every client renders
+//! (and mentions) the same thing.
+
+use serde::{Deserialize, Serialize};
+
+mod parser;
+
+pub use parser::{escape, parse};
+
+/// Bumped whenever the shape of the tree changes, so clients can tell what they're reading.
+pub const AST_VERSION: u32 = 1;
+pub const MAX_MENTION_ID_LENGTH: usize = 64;
+pub const MAX_URL_LENGTH: usize = 2048;
+
+/// Parsed message content.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Document {
+    pub version: u32,
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Text {
+        text: String,
+    },
+    Bold {
+        children: Vec<Node>,
+    },
+    Italic {
+        children: Vec<Node>,
+    },
+    Spoiler {
+        children: Vec<Node>,
+    },
+    /// Inline code, kept verbatim.
+    Code {
+        code: String,
+    },
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+    /// Consecutive lines starting with `> `.
+    Quote {
+        children: Vec<Node>,
+    },
+    UserMention {
+        id: String,
+    },
+    RoleMention {
+        id: String,
+    },
+    ChannelMention {
+        id: String,
+    },
+    Everyone,
+    /// Bare links have no children, clients show the url itself.
+    Link {
+        url: String,
+        children: Vec<Node>,
+    },
+}
+
+impl Node {
+    pub fn children(&self) -> &[Node] {
+        match self {
+            Node::Bold { children }
+            | Node::Italic { children }
+            | Node::Spoiler { children }
+            | Node::Quote { children }
+            | Node::Link { children, .. } => children,
+            _ => &[],
+        }
+    }
+}
+
+impl Document {
+    /// Calls `visit` on every node, parents before their children.
+    pub fn walk(&self, visit: &mut impl FnMut(&Node)) {
+        fn walk_nodes(nodes: &[Node], visit: &mut impl FnMut(&Node)) {
+            for node in nodes {
+                visit(node);
+                walk_nodes(node.children(), visit);
+            }
+        }
+
+        walk_nodes(&self.nodes, visit);
+    }
+}
+
+pub fn is_mention_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_MENTION_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
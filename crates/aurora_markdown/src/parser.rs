@@ -0,0 +1,444 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use crate::{is_mention_id, Document, Node, AST_VERSION, MAX_MENTION_ID_LENGTH, MAX_URL_LENGTH};
+
+const FENCE: &str = "```";
+const QUOTE: &str = "> ";
+
+/// Parses message content, anything which isn't valid markup is kept as text.
+pub fn parse(content: &str) -> Document {
+    Document {
+        version: AST_VERSION,
+        nodes: parse_blocks(content, true),
+    }
+}
+
+/// Escapes every character which could start markup, so `text` is shown as is.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Length of a backslash escape at the start of `rest`.
+fn escape_len(rest: &str) -> Option<usize> {
+    let mut chars = rest.chars();
+    (chars.next() == Some('\\') && chars.next().is_some_and(|c| c.is_ascii_punctuation()))
+        .then_some(2)
+}
+
+fn next_char_len(rest: &str) -> usize {
+    rest.chars().next().map_or(1, char::len_utf8)
+}
+
+/// Splits out code blocks and quotes, the text around them is parsed as inline markup.
+fn parse_blocks(content: &str, quotes: bool) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut run_start = 0;
+    let mut i = 0;
+    // once a fence has no closing one, none after it can have one either
+    let mut fences_closed = true;
+
+    while i < content.len() {
+        let rest = &content[i..];
+        let line_start = i == 0 || content.as_bytes()[i - 1] == b'\n';
+
+        if quotes && line_start && rest.starts_with(QUOTE) {
+            nodes.extend(parse_inline(&content[run_start..i], false));
+
+            let mut lines = Vec::new();
+            while content[i..].starts_with(QUOTE) {
+                let line_end = content[i..].find('\n').map(|end| i + end);
+                lines.push(&content[i + QUOTE.len()..line_end.unwrap_or(content.len())]);
+                i = line_end.map_or(content.len(), |end| end + 1);
+            }
+            nodes.push(Node::Quote {
+                children: parse_blocks(&lines.join("\n"), false),
+            });
+            run_start = i;
+            continue;
+        }
+
+        if let Some(len) = escape_len(rest) {
+            i += len;
+            continue;
+        }
+
+        if fences_closed && rest.starts_with(FENCE) {
+            match rest[FENCE.len()..].find(FENCE) {
+                Some(end) => {
+                    nodes.extend(parse_inline(&content[run_start..i], false));
+                    nodes.push(code_block(&rest[FENCE.len()..FENCE.len() + end]));
+                    i += end + FENCE.len() * 2;
+                    run_start = i;
+                    continue;
+                }
+                None => fences_closed = false,
+            }
+        }
+
+        i += next_char_len(rest);
+    }
+
+    nodes.extend(parse_inline(&content[run_start..], false));
+    nodes
+}
+
+fn is_language(header: &str) -> bool {
+    !header.is_empty()
+        && header.len() <= 32
+        && header
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-_.#".contains(c))
+}
+
+fn code_block(inner: &str) -> Node {
+    let (language, code) = match inner.split_once('\n') {
+        Some((header, code)) if is_language(header) => (Some(header.to_string()), code),
+        Some(("", code)) => (None, code),
+        _ => (None, inner),
+    };
+
+    Node::CodeBlock {
+        language,
+        code: code.strip_suffix('\n').unwrap_or(code).to_string(),
+    }
+}
+
+fn parse_inline(src: &str, in_link: bool) -> Vec<Node> {
+    Inline {
+        src,
+        in_link,
+        unclosed: Vec::new(),
+        brackets: bracket_pairs(src),
+    }
+    .parse()
+}
+
+/// Inline markup along with what's known about it, so a failed search for a closer isn't repeated.
+struct Inline<'a> {
+    src: &'a str,
+    /// Mentions and links in link text would let it pass for something else.
+    in_link: bool,
+    /// Delimiters which have no closer after where they were last tried.
+    unclosed: Vec<&'static str>,
+    /// The `]` closing each `[`, by index.
+    brackets: HashMap<usize, usize>,
+}
+
+impl Inline<'_> {
+    fn parse(mut self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let mut i = 0;
+
+        while i < self.src.len() {
+            let rest = &self.src[i..];
+
+            if let Some(len) = escape_len(rest) {
+                text.push_str(&rest[1..len]);
+                i += len;
+                continue;
+            }
+
+            if let Some((node, len)) = self.construct(i) {
+                if !text.is_empty() {
+                    nodes.push(Node::Text {
+                        text: std::mem::take(&mut text),
+                    });
+                }
+                nodes.push(node);
+                i += len;
+                continue;
+            }
+
+            let len = next_char_len(rest);
+            text.push_str(&rest[..len]);
+            i += len;
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text { text });
+        }
+        nodes
+    }
+
+    /// Markup starting at `src[i..]`, along with how much of it was used.
+    fn construct(&mut self, i: usize) -> Option<(Node, usize)> {
+        let rest = &self.src[i..];
+        let in_link = self.in_link;
+
+        match rest.as_bytes()[0] {
+            b'`' => inline_code(rest),
+            b'<' if !in_link => mention(rest).or_else(|| bracketed_link(rest)),
+            b'@' if !in_link && rest.starts_with("@everyone") => {
+                Some((Node::Everyone, "@everyone".len()))
+            }
+            b'h' if !in_link && !follows_word(self.src, i) => bare_link(rest),
+            b'[' if !in_link => masked_link(rest, self.brackets.get(&i)? - i),
+            b'|' => self
+                .delimited(i, "||")
+                .map(|(children, len)| (Node::Spoiler { children }, len)),
+            b'*' => self
+                .delimited(i, "**")
+                .map(|(children, len)| (Node::Bold { children }, len))
+                .or_else(|| {
+                    self.delimited(i, "*")
+                        .map(|(children, len)| (Node::Italic { children }, len))
+                }),
+            b'_' if !follows_word(self.src, i) => self
+                .delimited(i, "_")
+                .map(|(children, len)| (Node::Italic { children }, len)),
+            _ => None,
+        }
+    }
+
+    /// Formatting between a pair of `delimiter`s at `src[i..]`, along with how much of it was used.
+    ///
+    /// Delimiters pair with the nearest closer, so formatting never nests inside itself.
+    fn delimited(&mut self, i: usize, delimiter: &'static str) -> Option<(Vec<Node>, usize)> {
+        let rest = &self.src[i..];
+        let start = delimiter.len();
+        let emphasis = delimiter.len() == 1;
+        if !rest.starts_with(delimiter)
+            || (emphasis && rest[start..].starts_with(char::is_whitespace))
+            || self.unclosed.contains(&delimiter)
+        {
+            return None;
+        }
+
+        let mut i = start;
+        while i < rest.len() {
+            let tail = &rest[i..];
+            if let Some(len) = escape_len(tail).or_else(|| atomic(rest, i, self.in_link)) {
+                i += len;
+                continue;
+            }
+
+            // a bold delimiter can't close italics
+            if delimiter == "*" && tail.starts_with("**") {
+                i += 2;
+                continue;
+            }
+
+            if tail.starts_with(delimiter) && i > start && closes(rest, i, delimiter) {
+                let children = parse_inline(&rest[start..i], self.in_link);
+                return Some((children, i + delimiter.len()));
+            }
+
+            i += next_char_len(tail);
+        }
+
+        self.unclosed.push(delimiter);
+        None
+    }
+}
+
+fn follows_word(src: &str, i: usize) -> bool {
+    src[..i]
+        .chars()
+        .next_back()
+        .is_some_and(char::is_alphanumeric)
+}
+
+/// Markup which doesn't contain other markup, and is skipped over when looking for a closing delimiter.
+fn atomic(src: &str, i: usize, in_link: bool) -> Option<usize> {
+    let rest = &src[i..];
+    let (_, len) = match rest.as_bytes()[0] {
+        b'`' => inline_code(rest),
+        b'<' if !in_link => mention(rest).or_else(|| bracketed_link(rest)),
+        b'h' if !in_link && !follows_word(src, i) => bare_link(rest),
+        _ => None,
+    }?;
+    Some(len)
+}
+
+fn inline_code(rest: &str) -> Option<(Node, usize)> {
+    let inner = rest.strip_prefix('`')?;
+    let end = inner.find(['`', '\n'])?;
+    if end == 0 || inner.as_bytes()[end] != b'`' {
+        return None;
+    }
+
+    Some((
+        Node::Code {
+            code: inner[..end].to_string(),
+        },
+        end + 2,
+    ))
+}
+
+fn mention(rest: &str) -> Option<(Node, usize)> {
+    let (prefix, make): (&str, fn(String) -> Node) = if rest.starts_with("<@&") {
+        ("<@&", |id| Node::RoleMention { id })
+    } else if rest.starts_with("<@") {
+        ("<@", |id| Node::UserMention { id })
+    } else if rest.starts_with("<#") {
+        ("<#", |id| Node::ChannelMention { id })
+    } else {
+        return None;
+    };
+
+    let after = &rest[prefix.len()..];
+    let len = after
+        .bytes()
+        .take(MAX_MENTION_ID_LENGTH + 1)
+        .take_while(|b| b.is_ascii_alphanumeric() || *b == b'-')
+        .count();
+    let id = &after[..len];
+    if !is_mention_id(id) || after.as_bytes().get(len) != Some(&b'>') {
+        return None;
+    }
+
+    Some((make(id.to_string()), prefix.len() + len + 1))
+}
+
+fn is_url(url: &str) -> bool {
+    let address = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    address.is_some_and(|address| !address.is_empty())
+        && url.len() <= MAX_URL_LENGTH
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// `<https://...>`, which clients don't preview.
+fn bracketed_link(rest: &str) -> Option<(Node, usize)> {
+    let inner = rest.strip_prefix('<')?;
+    let end = inner.find(|c: char| c == '>' || c == '<' || c.is_whitespace())?;
+    let url = &inner[..end];
+    if inner.as_bytes()[end] != b'>' || !is_url(url) {
+        return None;
+    }
+
+    Some((
+        Node::Link {
+            url: url.to_string(),
+            children: Vec::new(),
+        },
+        end + 2,
+    ))
+}
+
+fn bare_link(rest: &str) -> Option<(Node, usize)> {
+    if !rest.starts_with("http://") && !rest.starts_with("https://") {
+        return None;
+    }
+
+    let end = rest
+        .find(|c: char| c == '<' || c == '>' || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let mut url = &rest[..end];
+    // trailing punctuation belongs to the sentence, or closes formatting around the link
+    loop {
+        if let Some(trimmed) = url.strip_suffix(|c: char| ".,:;!?'\"*_|~".contains(c)) {
+            url = trimmed;
+        } else if url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+            url = &url[..url.len() - 1];
+        } else {
+            break;
+        }
+    }
+
+    is_url(url).then(|| {
+        (
+            Node::Link {
+                url: url.to_string(),
+                children: Vec::new(),
+            },
+            url.len(),
+        )
+    })
+}
+
+/// `[text](https://...)`, where `text_end` is the index of the `]` closing the text.
+fn masked_link(rest: &str, text_end: usize) -> Option<(Node, usize)> {
+    let text = &rest[1..text_end];
+    let target = rest[text_end + 1..].strip_prefix('(')?;
+
+    let mut parens = 0usize;
+    let mut url_end = None;
+    for (i, c) in target.char_indices() {
+        match c {
+            '(' => parens += 1,
+            ')' if parens == 0 => {
+                url_end = Some(i);
+                break;
+            }
+            ')' => parens -= 1,
+            c if c.is_whitespace() => return None,
+            _ => {}
+        }
+    }
+    let url = &target[..url_end?];
+    if text.trim().is_empty() || !is_url(url) {
+        return None;
+    }
+
+    Some((
+        Node::Link {
+            url: url.to_string(),
+            children: parse_inline(text, true),
+        },
+        text_end + url.len() + 3,
+    ))
+}
+
+/// Pairs up the brackets in `src`, skipping escaped ones and those in code.
+fn bracket_pairs(src: &str) -> HashMap<usize, usize> {
+    let mut pairs = HashMap::new();
+    let mut open = Vec::new();
+    let mut i = 0;
+
+    while i < src.len() {
+        let rest = &src[i..];
+        if let Some(len) = escape_len(rest).or_else(|| inline_code(rest).map(|(_, len)| len)) {
+            i += len;
+            continue;
+        }
+
+        match rest.as_bytes()[0] {
+            b'[' => open.push(i),
+            b']' => {
+                if let Some(start) = open.pop() {
+                    pairs.insert(start, i);
+                }
+            }
+            _ => {}
+        }
+        i += next_char_len(rest);
+    }
+
+    pairs
+}
+
+fn closes(rest: &str, i: usize, delimiter: &str) -> bool {
+    match delimiter {
+        "*" => !rest[..i].ends_with(char::is_whitespace),
+        "_" => {
+            !rest[i + 1..].starts_with(char::is_alphanumeric)
+                && !rest[..i].ends_with(char::is_whitespace)
+        }
+        _ => true,
+    }
+}
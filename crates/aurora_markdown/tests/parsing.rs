@@ -0,0 +1,190 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_markdown::{parse, Node, AST_VERSION};
+
+fn text(text: &str) -> Node {
+    Node::Text {
+        text: text.to_string(),
+    }
+}
+
+fn nodes(content: &str) -> Vec<Node> {
+    let document = parse(content);
+    assert_eq!(document.version, AST_VERSION);
+    document.nodes
+}
+
+#[test]
+fn formatting_nests() {
+    assert_eq!(
+        nodes("**bold *italic* ||secret||** _also italic_"),
+        vec![
+            Node::Bold {
+                children: vec![
+                    text("bold "),
+                    Node::Italic {
+                        children: vec![text("italic")]
+                    },
+                    text(" "),
+                    Node::Spoiler {
+                        children: vec![text("secret")]
+                    },
+                ]
+            },
+            text(" "),
+            Node::Italic {
+                children: vec![text("also italic")]
+            },
+        ]
+    );
+
+    // not emphasis: spaced out operators, snake case and unclosed delimiters
+    assert_eq!(nodes("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+    assert_eq!(nodes("snake_case_name"), vec![text("snake_case_name")]);
+    assert_eq!(nodes("**open ||too"), vec![text("**open ||too")]);
+    assert_eq!(nodes(r"\*\*not bold\*\*"), vec![text("**not bold**")]);
+}
+
+#[test]
+fn code_is_verbatim() {
+    assert_eq!(
+        nodes("run `<@1> **x**` then\n```rust\nfn main() {}\n```"),
+        vec![
+            text("run "),
+            Node::Code {
+                code: "<@1> **x**".to_string()
+            },
+            text(" then\n"),
+            Node::CodeBlock {
+                language: Some("rust".to_string()),
+                code: "fn main() {}".to_string()
+            },
+        ]
+    );
+
+    // a code block's first line is only a language if it looks like one
+    assert_eq!(
+        nodes("```not a language\n@everyone```"),
+        vec![Node::CodeBlock {
+            language: None,
+            code: "not a language\n@everyone".to_string()
+        }]
+    );
+}
+
+#[test]
+fn quotes_group_lines() {
+    assert_eq!(
+        nodes("> first\n> **second**\nafter\n>not a quote"),
+        vec![
+            Node::Quote {
+                children: vec![
+                    text("first\n"),
+                    Node::Bold {
+                        children: vec![text("second")]
+                    },
+                ]
+            },
+            text("after\n>not a quote"),
+        ]
+    );
+
+    // a fenced block keeps its own `> ` lines
+    assert_eq!(
+        nodes("```\n> code\n```"),
+        vec![Node::CodeBlock {
+            language: None,
+            code: "> code".to_string()
+        }]
+    );
+}
+
+#[test]
+fn mentions() {
+    assert_eq!(
+        nodes("<@123> <@&456> <#789> @everyone <@bad id> <@>"),
+        vec![
+            Node::UserMention {
+                id: "123".to_string()
+            },
+            text(" "),
+            Node::RoleMention {
+                id: "456".to_string()
+            },
+            text(" "),
+            Node::ChannelMention {
+                id: "789".to_string()
+            },
+            text(" "),
+            Node::Everyone,
+            text(" <@bad id> <@>"),
+        ]
+    );
+}
+
+#[test]
+fn links() {
+    assert_eq!(
+        nodes("see https://example.com/a_(b)). or <https://example.com> or [**docs**](https://example.com/docs)"),
+        vec![
+            text("see "),
+            Node::Link {
+                url: "https://example.com/a_(b)".to_string(),
+                children: vec![]
+            },
+            text("). or "),
+            Node::Link {
+                url: "https://example.com".to_string(),
+                children: vec![]
+            },
+            text(" or "),
+            Node::Link {
+                url: "https://example.com/docs".to_string(),
+                children: vec![Node::Bold {
+                    children: vec![text("docs")]
+                }]
+            },
+        ]
+    );
+
+    // link text can't hold mentions or other links, and only http(s) is linked
+    assert_eq!(
+        nodes("[<@1>](https://a.com) [x](javascript:alert(1))"),
+        vec![
+            Node::Link {
+                url: "https://a.com".to_string(),
+                children: vec![text("<@1>")]
+            },
+            text(" [x](javascript:alert(1))"),
+        ]
+    );
+}
+
+#[test]
+fn delimiters_pair_with_the_nearest_closer() {
+    assert_eq!(
+        nodes("||a ||b|| c||"),
+        vec![
+            Node::Spoiler {
+                children: vec![text("a ")]
+            },
+            text("b"),
+            Node::Spoiler {
+                children: vec![text(" c")]
+            },
+        ]
+    );
+}
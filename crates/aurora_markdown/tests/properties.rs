@@ -0,0 +1,141 @@
+// Copyright (C) 2024 V.J. De Chico
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use aurora_markdown::{escape, is_mention_id, parse, Document, Node, AST_VERSION};
+use proptest::prelude::*;
+
+/// Content made mostly of markup, which finds far more edge cases than arbitrary text.
+fn markup() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        Just("*".to_string()),
+        Just("**".to_string()),
+        Just("_".to_string()),
+        Just("||".to_string()),
+        Just("`".to_string()),
+        Just("```".to_string()),
+        Just("> ".to_string()),
+        Just("\n".to_string()),
+        Just("\\".to_string()),
+        Just("[".to_string()),
+        Just("](".to_string()),
+        Just(")".to_string()),
+        Just("<@1>".to_string()),
+        Just("<@&2>".to_string()),
+        Just("<#3>".to_string()),
+        Just("<".to_string()),
+        Just(">".to_string()),
+        Just("@everyone".to_string()),
+        Just("https://a.com".to_string()),
+        Just(" ".to_string()),
+        "[a-z0-9]{1,3}",
+        "\\PC",
+    ];
+    prop::collection::vec(piece, 0..64).prop_map(|pieces| pieces.concat())
+}
+
+fn any_content() -> impl Strategy<Value = String> {
+    prop_oneof![markup(), any::<String>()]
+}
+
+fn same_kind(a: &Node, b: &Node) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Checks structural invariants of `nodes`, returning how deeply they nest.
+fn check(nodes: &[Node], ancestors: &mut Vec<Node>) -> usize {
+    let mut depth = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Text { text } = node {
+            assert!(!text.is_empty(), "empty text");
+            assert!(
+                !matches!(nodes.get(i + 1), Some(Node::Text { .. })),
+                "adjacent text"
+            );
+        }
+        if !matches!(node, Node::Italic { .. }) {
+            assert!(
+                !ancestors.iter().any(|ancestor| same_kind(ancestor, node)),
+                "{node:?} nested in itself"
+            );
+        }
+
+        if !node.children().is_empty() {
+            ancestors.push(node.clone());
+            depth = depth.max(1 + check(node.children(), ancestors));
+            ancestors.pop();
+        }
+    }
+    depth
+}
+
+proptest! {
+    #[test]
+    fn parses_anything(content in any_content()) {
+        let document = parse(&content);
+        prop_assert_eq!(document.version, AST_VERSION);
+        // quote, link, spoiler, bold and both kinds of italics
+        prop_assert!(check(&document.nodes, &mut Vec::new()) <= 6);
+    }
+
+    #[test]
+    fn mentions_come_from_content(content in any_content()) {
+        let mut nodes = Vec::new();
+        parse(&content).walk(&mut |node| nodes.push(node.clone()));
+
+        for node in nodes {
+            let source = match node {
+                Node::UserMention { id } => {
+                    prop_assert!(is_mention_id(&id));
+                    format!("<@{id}>")
+                }
+                Node::RoleMention { id } => {
+                    prop_assert!(is_mention_id(&id));
+                    format!("<@&{id}>")
+                }
+                Node::ChannelMention { id } => {
+                    prop_assert!(is_mention_id(&id));
+                    format!("<#{id}>")
+                }
+                Node::Everyone => "@everyone".to_string(),
+                _ => continue,
+            };
+            prop_assert!(content.contains(&source));
+        }
+    }
+
+    #[test]
+    fn escaped_text_is_text(text in any_content().prop_filter("empty", |text| !text.is_empty())) {
+        prop_assert_eq!(parse(&escape(&text)).nodes, vec![Node::Text { text }]);
+    }
+
+    #[test]
+    fn code_hides_markup(code in "[^`\n]+", block in "[^`]*") {
+        prop_assert_eq!(
+            parse(&format!("`{code}`")).nodes,
+            vec![Node::Code { code }]
+        );
+        prop_assert_eq!(
+            parse(&format!("```\n{block}\n```")).nodes,
+            vec![Node::CodeBlock { language: None, code: block }]
+        );
+    }
+
+    #[test]
+    fn serializes_losslessly(content in any_content()) {
+        let document = parse(&content);
+        let json = serde_json::to_string(&document).unwrap();
+        prop_assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+    }
+}
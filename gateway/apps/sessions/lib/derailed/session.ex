@@ -63,6 +63,38 @@ defmodule Derailed.Session do
 
     {:ok, guilds} = Derailed.DB.maps(result)
 
+    # unread counts are capped at 100, matching ReadState in aurora_db
+    {_, result} =
+      Postgrex.prepare_execute!(
+        :db,
+        "get_read_states_session_genserver",
+        """
+        SELECT
+          c.id AS channel_id,
+          rs.last_message_id,
+          (SELECT COUNT(*) FROM (
+            SELECT 1 FROM messages m
+            WHERE m.channel_id = c.id
+              AND (rs.last_message_id IS NULL OR m.id COLLATE "C" > rs.last_message_id)
+              AND m.author_id IS DISTINCT FROM $1
+            LIMIT 100
+          ) unread) AS unread_count,
+          (SELECT COUNT(*) FROM messages m
+            WHERE m.channel_id = c.id
+              AND (rs.last_message_id IS NULL OR m.id COLLATE "C" > rs.last_message_id)
+              AND m.author_id IS DISTINCT FROM $1
+              AND ($1 = ANY(m.mentions) OR m.mention_everyone)
+          ) AS mention_count
+        FROM channels c
+        JOIN guild_members gm ON gm.guild_id = c.guild_id AND gm.user_id = $1
+        LEFT JOIN read_states rs ON rs.channel_id = c.id AND rs.user_id = $1
+        WHERE c.last_message_id IS NOT NULL;
+        """,
+        [user_id]
+      )
+
+    {:ok, read_states} = Derailed.DB.maps(result)
+
     guild_pids =
       Enum.map(guilds, fn g ->
         {:ok, pid} = GenRegistry.lookup_or_start(Derailed.Guild, g["id"], [{g["id"], g}])
@@ -78,6 +110,7 @@ defmodule Derailed.Session do
        actor_data: actor,
        # relationship_data: relationships,
        guild_data: guilds,
+       read_state_data: read_states,
        guild_pids: guild_pids,
        guild_refs: guild_refs,
        ws_pid: ws_pid,
@@ -97,7 +130,8 @@ defmodule Derailed.Session do
       %{
         relationships: state[:relationship_data],
         user: state[:user_data],
-        guilds: state[:guild_data]
+        guilds: state[:guild_data],
+        read_states: state[:read_state_data]
       }
     })
 
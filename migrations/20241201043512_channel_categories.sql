@@ -0,0 +1,5 @@
+ALTER TABLE channels
+ADD type INTEGER NOT NULL DEFAULT 0;
+-- categories
+ALTER TABLE channels
+ADD parent_id TEXT REFERENCES channels(id) ON DELETE SET NULL;
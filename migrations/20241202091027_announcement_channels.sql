@@ -0,0 +1,19 @@
+CREATE TABLE channel_followers (
+    -- the announcement channel being followed
+    channel_id TEXT NOT NULL,
+    target_guild_id TEXT NOT NULL,
+    target_channel_id TEXT NOT NULL,
+    FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_guild_id) REFERENCES guilds(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_channel_id) REFERENCES channels(id) ON DELETE CASCADE,
+    PRIMARY KEY (channel_id, target_channel_id)
+);
+ALTER TABLE messages
+ADD flags INTEGER NOT NULL DEFAULT 0;
+-- cross-posted messages point back at where they were published
+ALTER TABLE messages
+ADD source_guild_id TEXT REFERENCES guilds(id) ON DELETE SET NULL;
+ALTER TABLE messages
+ADD source_channel_id TEXT;
+ALTER TABLE messages
+ADD source_message_id TEXT;
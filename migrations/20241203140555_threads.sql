@@ -0,0 +1,23 @@
+CREATE TABLE thread_metadata (
+    id TEXT PRIMARY KEY,
+    -- the message this thread was spawned from
+    message_id TEXT UNIQUE,
+    owner_id TEXT NOT NULL,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    -- minutes of inactivity before the thread is archived
+    auto_archive_duration INTEGER NOT NULL,
+    archived_at TIMESTAMPTZ,
+    last_activity_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE SET NULL,
+    FOREIGN KEY (owner_id) REFERENCES actors(id) ON DELETE CASCADE
+);
+CREATE TABLE thread_members (
+    thread_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (thread_id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES actors(id) ON DELETE CASCADE,
+    PRIMARY KEY (thread_id, user_id)
+);
+CREATE INDEX thread_metadata_active_idx ON thread_metadata (last_activity_at) WHERE NOT archived;
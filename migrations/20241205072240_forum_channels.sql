@@ -0,0 +1,16 @@
+CREATE TABLE guild_tags (
+    id TEXT PRIMARY KEY,
+    guild_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+CREATE TABLE thread_tags (
+    thread_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL,
+    FOREIGN KEY (thread_id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES guild_tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (thread_id, tag_id)
+);
+-- only used by forum posts
+ALTER TABLE thread_metadata
+ADD solved BOOLEAN NOT NULL DEFAULT FALSE;
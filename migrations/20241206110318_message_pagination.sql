@@ -0,0 +1,2 @@
+-- uuid7 ids sort chronologically under byte ordering, so pagination compares them with the "C" collation
+CREATE INDEX messages_channel_id_id_idx ON messages (channel_id, id COLLATE "C");
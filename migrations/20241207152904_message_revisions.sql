@@ -0,0 +1,12 @@
+ALTER TABLE messages
+ADD edited_at TIMESTAMPTZ;
+-- content a message had before each edit
+CREATE TABLE message_revisions (
+    id TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL,
+    content TEXT NOT NULL,
+    -- when this content was replaced
+    replaced_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+);
+CREATE INDEX message_revisions_message_id_idx ON message_revisions (message_id);
@@ -0,0 +1,18 @@
+CREATE TABLE message_reactions (
+    message_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    -- a unicode emoji, or name:id for custom emojis
+    emoji TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES actors(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, emoji, user_id)
+);
+-- kept up to date alongside message_reactions so popular messages don't need counting
+CREATE TABLE message_reaction_counts (
+    message_id TEXT NOT NULL,
+    emoji TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, emoji)
+);
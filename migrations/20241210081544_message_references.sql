@@ -0,0 +1,3 @@
+-- not a foreign key, so replies keep pointing at messages which have since been deleted
+ALTER TABLE messages
+ADD reference_id TEXT;
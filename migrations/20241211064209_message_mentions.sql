@@ -0,0 +1,6 @@
+ALTER TABLE messages
+ADD mentions TEXT[] NOT NULL DEFAULT '{}',
+ADD mention_roles TEXT[] NOT NULL DEFAULT '{}',
+ADD mention_channels TEXT[] NOT NULL DEFAULT '{}',
+ADD mention_everyone BOOLEAN NOT NULL DEFAULT false;
+CREATE INDEX messages_mentions_idx ON messages USING GIN (mentions);
@@ -0,0 +1,3 @@
+ALTER TABLE messages
+ADD pinned_at TIMESTAMPTZ;
+CREATE INDEX messages_pins_idx ON messages (channel_id, pinned_at) WHERE pinned_at IS NOT NULL;
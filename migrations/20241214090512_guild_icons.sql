@@ -0,0 +1,2 @@
+ALTER TABLE guilds
+ADD icon_url TEXT;
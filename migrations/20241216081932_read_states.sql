@@ -0,0 +1,10 @@
+CREATE TABLE read_states (
+    user_id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    -- the newest message the user has seen, which may no longer exist
+    last_message_id TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (user_id) REFERENCES actors(id) ON DELETE CASCADE,
+    FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, channel_id)
+);
@@ -0,0 +1,20 @@
+CREATE TABLE webhooks (
+    id TEXT PRIMARY KEY,
+    guild_id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    creator_id TEXT,
+    name TEXT NOT NULL,
+    avatar_url TEXT,
+    -- sha256 of the secret token, which is only shown once on creation
+    token_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE,
+    FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (creator_id) REFERENCES actors(id) ON DELETE SET NULL
+);
+CREATE INDEX webhooks_channel_id_idx ON webhooks (channel_id);
+-- messages keep their webhook's look after it's modified or deleted
+ALTER TABLE messages
+ADD webhook_id TEXT,
+ADD webhook_name TEXT,
+ADD webhook_avatar_url TEXT;
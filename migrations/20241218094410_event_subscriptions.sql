@@ -0,0 +1,34 @@
+CREATE TABLE event_subscriptions (
+    id TEXT PRIMARY KEY,
+    guild_id TEXT NOT NULL,
+    creator_id TEXT,
+    url TEXT NOT NULL,
+    -- signs deliveries, only shown once on creation
+    secret TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    -- failed attempts since the last successful delivery
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE,
+    FOREIGN KEY (creator_id) REFERENCES actors(id) ON DELETE SET NULL
+);
+CREATE INDEX event_subscriptions_guild_id_idx ON event_subscriptions (guild_id);
+CREATE TABLE event_deliveries (
+    id TEXT PRIMARY KEY,
+    subscription_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    -- the exact body which gets signed and sent
+    payload TEXT NOT NULL,
+    -- pending, succeeded or failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ,
+    FOREIGN KEY (subscription_id) REFERENCES event_subscriptions(id) ON DELETE CASCADE
+);
+CREATE INDEX event_deliveries_subscription_id_idx ON event_deliveries (subscription_id, id COLLATE "C");
+CREATE INDEX event_deliveries_pending_idx ON event_deliveries (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,46 @@
+CREATE TABLE applications (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    -- the account the application acts as, sharing its id with its actor
+    bot_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- interactions go over the gateway unless this is set
+    interactions_url TEXT,
+    -- signs interactions sent to interactions_url
+    interactions_secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (owner_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY (bot_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+CREATE TABLE application_commands (
+    id TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL,
+    -- global commands have no guild
+    guild_id TEXT,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    options JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (application_id) REFERENCES applications(id) ON DELETE CASCADE,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE,
+    UNIQUE NULLS NOT DISTINCT (application_id, guild_id, name)
+);
+CREATE TABLE interactions (
+    id TEXT PRIMARY KEY,
+    application_id TEXT NOT NULL,
+    command_id TEXT NOT NULL,
+    guild_id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    -- sha256 of the token the application responds with
+    token_hash TEXT NOT NULL,
+    responded BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (application_id) REFERENCES applications(id) ON DELETE CASCADE,
+    FOREIGN KEY (command_id) REFERENCES application_commands(id) ON DELETE CASCADE,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE,
+    FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES actors(id) ON DELETE CASCADE
+);
+ALTER TABLE messages
+ADD interaction_id TEXT;
@@ -0,0 +1,9 @@
+ALTER TABLE messages
+ADD components JSONB;
+-- component interactions come from a message rather than a command
+ALTER TABLE interactions
+ALTER command_id DROP NOT NULL,
+ADD message_id TEXT,
+ADD custom_id TEXT,
+ADD component_values TEXT[] NOT NULL DEFAULT '{}',
+ADD FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE;
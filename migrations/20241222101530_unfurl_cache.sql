@@ -0,0 +1,6 @@
+-- link previews by url, a NULL embed means the url had nothing to show
+CREATE TABLE unfurl_cache (
+    url TEXT PRIMARY KEY,
+    embed JSONB,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
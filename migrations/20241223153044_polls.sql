@@ -0,0 +1,31 @@
+CREATE TABLE polls (
+    message_id TEXT PRIMARY KEY,
+    -- kept so closing polls knows where to publish results
+    guild_id TEXT NOT NULL,
+    question TEXT NOT NULL,
+    allow_multiselect BOOLEAN NOT NULL DEFAULT false,
+    expires_at TIMESTAMPTZ NOT NULL,
+    closed BOOLEAN NOT NULL DEFAULT false,
+    FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+CREATE INDEX polls_open_idx ON polls (expires_at) WHERE NOT closed;
+CREATE TABLE poll_answers (
+    message_id TEXT NOT NULL,
+    -- numbered from 1 in the order the answers were given
+    answer_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    -- kept up to date alongside poll_votes, like reaction counts
+    vote_count INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (message_id) REFERENCES polls(message_id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, answer_id)
+);
+CREATE TABLE poll_votes (
+    message_id TEXT NOT NULL,
+    answer_id INTEGER NOT NULL,
+    user_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (message_id, answer_id) REFERENCES poll_answers(message_id, answer_id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES actors(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, answer_id, user_id)
+);
@@ -0,0 +1,2 @@
+-- guilds created before ADD_REACTIONS existed don't have it yet
+UPDATE guilds SET permissions = permissions | 256;
@@ -0,0 +1,2 @@
+-- guilds created before MENTION_EVERYONE existed don't have it yet
+UPDATE guilds SET permissions = permissions | 512;
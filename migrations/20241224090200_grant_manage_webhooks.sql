@@ -0,0 +1,2 @@
+-- guilds created before MANAGE_WEBHOOKS existed don't have it yet
+UPDATE guilds SET permissions = permissions | 1024;